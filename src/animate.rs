@@ -0,0 +1,120 @@
+use std::io::Write;
+use std::time::Duration;
+
+use crate::image::{self, Image};
+use crate::visualize::Palette;
+
+/// A puzzle simulation that can be played back one step at a time.
+pub trait Simulation {
+    /// Advances the simulation by one step, returning `false` once
+    /// there are no steps left to take.
+    fn step(&mut self) -> bool;
+
+    /// Draws the current state of the simulation as a single frame.
+    fn render(&self, palette: &Palette) -> String;
+
+    /// Draws the current state of the simulation as an image, if the
+    /// simulation supports it.
+    fn image(&self) -> Option<Image> {
+        None
+    }
+}
+
+/// Which frames of a simulation to play, and how quickly.
+#[derive(Debug, Clone, Copy)]
+pub struct Playback {
+    pub fps: f32,
+    pub from: usize,
+    pub to: Option<usize>,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            fps: 10.0,
+            from: 0,
+            to: None,
+        }
+    }
+}
+
+impl Playback {
+    /// Runs the simulation, handing the simulation at each selected frame
+    /// and its step number to `on_frame`. Frame 0 is the state before the
+    /// first step.
+    fn for_each_frame(
+        &self,
+        simulation: &mut dyn Simulation,
+        mut on_frame: impl FnMut(usize, &dyn Simulation) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        let mut step = 0;
+
+        loop {
+            if step >= self.from {
+                on_frame(step, simulation)?;
+            }
+
+            if self.to.is_some_and(|to| step >= to) || !simulation.step() {
+                return Ok(());
+            }
+
+            step += 1;
+        }
+    }
+
+    /// Redraws the terminal with each frame at the configured frame rate.
+    pub fn animate(
+        &self,
+        simulation: &mut dyn Simulation,
+        palette: &Palette,
+    ) -> std::io::Result<()> {
+        let frame_duration = Duration::from_secs_f32(1.0 / self.fps);
+        let mut stdout = std::io::stdout().lock();
+
+        self.for_each_frame(simulation, |step, simulation| {
+            let frame = simulation.render(palette);
+            write!(stdout, "\x1b[2J\x1b[H{frame}\nStep {step}\n")?;
+            stdout.flush()?;
+            std::thread::sleep(frame_duration);
+
+            Ok(())
+        })
+    }
+
+    /// Writes each frame to the writer without pausing, each one
+    /// preceded by a header line with its step number.
+    pub fn dump(
+        &self,
+        simulation: &mut dyn Simulation,
+        mut writer: impl Write,
+    ) -> std::io::Result<()> {
+        let palette = Palette::new(false);
+        self.for_each_frame(simulation, |step, simulation| {
+            writeln!(writer, "Step {step}\n{}\n", simulation.render(&palette))
+        })
+    }
+
+    /// Writes the frames as an animated GIF playing at the configured
+    /// frame rate, with each pixel scaled up by `scale`.
+    pub fn dump_gif(
+        &self,
+        simulation: &mut dyn Simulation,
+        scale: usize,
+        writer: impl Write,
+    ) -> std::io::Result<()> {
+        let mut frames = vec![];
+        self.for_each_frame(simulation, |_step, simulation| {
+            let image = simulation.image().ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "Simulation can't be drawn as an image",
+                )
+            })?;
+            frames.push(image.scaled(scale));
+
+            Ok(())
+        })?;
+
+        image::write_gif(&frames, Duration::from_secs_f32(1.0 / self.fps), writer)
+    }
+}
@@ -2,6 +2,9 @@ use nom::bytes::complete::tag;
 use nom::combinator::map;
 use nom::{IResult, Parser};
 
+use crate::image::{Image, Rgb};
+use crate::util::{ocr, Coordinates, Matrix};
+use crate::visualize::{render_rows, Color, Palette, Visualize};
 use crate::{DaySolution, FromInput};
 
 pub struct Day10(Vec<Instruction>);
@@ -52,29 +55,76 @@ impl DaySolution for Day10 {
     }
 
     fn part_two(&self) -> String {
-        let chars = self
-            .calculate_x_for_each_cycle()
-            .take(240)
-            .map(|(cycle, x)| {
-                if ((cycle % 40) as isize - x).abs() <= 1 {
-                    '#'
+        let pixels = self.crt_pixels();
+
+        // Fall back to the raw picture when it doesn't spell out letters
+        ocr::decode(&pixels).unwrap_or_else(|_error| {
+            let picture = pixels
+                .rows()
+                .map(|row| row.iter().map(|lit| if *lit { '#' } else { '.' }).collect())
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            format!("\n{picture}\n")
+        })
+    }
+
+    fn visualization(&self) -> Option<&dyn Visualize> {
+        Some(self)
+    }
+}
+
+/// Draws the letters on the CRT screen, followed by what they read as
+/// or why they couldn't be read.
+impl Visualize for Day10 {
+    fn visualize(&self, palette: &Palette) -> String {
+        let pixels = self.crt_pixels();
+        let screen = render_rows(pixels.rows().map(|row| {
+            row.iter().map(|lit| {
+                if *lit {
+                    palette.paint('#', Color::Green)
                 } else {
-                    '.'
+                    ".".to_owned()
                 }
             })
-            .collect::<Vec<char>>();
+        }));
 
-        let x = chars
-            .chunks(40)
-            .map(|c| c.iter().collect())
-            .collect::<Vec<String>>()
-            .join("\n");
+        let reading = match ocr::decode(&pixels) {
+            Ok(letters) => format!("Reads as: {letters}"),
+            Err(error) => format!("Can't read the letters: {error}"),
+        };
 
-        format!("\n{x}\n")
+        format!("{screen}\n\n{reading}")
+    }
+
+    fn image(&self) -> Option<Image> {
+        Some(Image::from_matrix(&self.crt_pixels(), |(_coords, lit)| {
+            if *lit {
+                Rgb::new(80, 255, 120)
+            } else {
+                Rgb::new(10, 20, 10)
+            }
+        }))
     }
 }
 
 impl Day10 {
+    /// Which pixels of the 40 by 6 screen are lit.
+    fn crt_pixels(&self) -> Matrix<bool> {
+        let mut pixels = Matrix::new(Coordinates { x: 40, y: 6 }, || false);
+
+        // Short programs leave the rest of the screen dark
+        for (cycle, x) in self.calculate_x_for_each_cycle().take(240) {
+            let coords = Coordinates {
+                x: (cycle % 40) as isize,
+                y: (cycle / 40) as isize,
+            };
+            pixels[coords] = (coords.x - x).abs() <= 1;
+        }
+
+        pixels
+    }
+
     fn calculate_x_for_each_cycle<'d>(&'d self) -> impl 'd + Iterator<Item = (usize, isize)> {
         self.0
             .iter()
@@ -1,9 +1,15 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+
 use nom::bytes::complete::tag;
-use nom::character::complete::digit1;
+use nom::character::complete::{anychar, digit1, space0};
 use nom::combinator::{map, map_res};
 use nom::multi::separated_list1;
+use nom::sequence::delimited;
 use nom::{IResult, Parser};
 
+use crate::trace::Trace;
 use crate::{DaySolution, FromInput};
 
 #[derive(Clone, Debug)]
@@ -61,62 +67,170 @@ impl Monkey {
     }
 }
 
+/// How a monkey changes an item's worry level, as an arithmetic
+/// expression of the old worry level.
 #[derive(Clone, Debug)]
-struct Operation {
-    operator: Operator,
-    operand: Operand,
-}
+struct Operation(Expression);
 
 impl Operation {
     fn parse(input: &str) -> IResult<&str, Self> {
-        let (input, _) = tag("new = old ")(input)?;
-        let (input, operator) = Operator::parse(input)?;
-        let (input, _) = tag(" ")(input)?;
-        let (input, operand) = Operand::parse(input)?;
+        let (input, _) = tag("new = ")(input)?;
+        let (input, expression) = Expression::parse(input)?;
 
-        Ok((input, Self { operator, operand }))
+        Ok((input, Self(expression)))
     }
 
-    fn apply(&self, worry: usize) -> usize {
-        let second = match self.operand {
-            Operand::Old => worry,
-            Operand::Num(num) => num,
-        };
+    fn apply(&self, worry: usize) -> Result<usize, ArithmeticError> {
+        self.0.evaluate(worry)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Expression {
+    Old,
+    Num(usize),
+    Binary(Box<Expression>, Operator, Box<Expression>),
+}
+
+impl Expression {
+    /// Parses an expression where `*`, `/`, and `%` bind tighter than
+    /// `+` and `-`, and operators of the same precedence apply left to right.
+    fn parse(input: &str) -> IResult<&str, Self> {
+        Self::parse_binary(
+            input,
+            &[Operator::Add, Operator::Subtract],
+            Self::parse_term,
+        )
+    }
 
-        match self.operator {
-            Operator::Multiply => worry * second,
-            Operator::Add => worry + second,
+    fn parse_term(input: &str) -> IResult<&str, Self> {
+        Self::parse_binary(
+            input,
+            &[Operator::Multiply, Operator::Divide, Operator::Remainder],
+            Self::parse_factor,
+        )
+    }
+
+    fn parse_factor(input: &str) -> IResult<&str, Self> {
+        let parse_old = map(tag("old"), |_| Expression::Old);
+        let parse_num = map(parse_usize, Expression::Num);
+        let parse_group = delimited(tag("(").and(space0), Self::parse, space0.and(tag(")")));
+
+        parse_old.or(parse_num).or(parse_group).parse(input)
+    }
+
+    fn parse_binary<'i>(
+        input: &'i str,
+        operators: &[Operator],
+        mut parse_operand: impl FnMut(&'i str) -> IResult<&'i str, Self>,
+    ) -> IResult<&'i str, Self> {
+        let (mut input, mut expression) = parse_operand(input)?;
+
+        loop {
+            let (rest, _) = space0(input)?;
+            let Ok((rest, operator)) = Operator::parse(rest) else {
+                return Ok((input, expression));
+            };
+            if !operators.contains(&operator) {
+                return Ok((input, expression));
+            }
+
+            let (rest, _) = space0(rest)?;
+            let (rest, right) = parse_operand(rest)?;
+            expression = Expression::Binary(Box::new(expression), operator, Box::new(right));
+            input = rest;
+        }
+    }
+
+    fn evaluate(&self, old: usize) -> Result<usize, ArithmeticError> {
+        match self {
+            Expression::Old => Ok(old),
+            Expression::Num(num) => Ok(*num),
+            Expression::Binary(left, operator, right) => {
+                operator.apply(left.evaluate(old)?, right.evaluate(old)?)
+            }
         }
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum Operator {
-    Multiply,
     Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
 }
 
 impl Operator {
     fn parse(input: &str) -> IResult<&str, Self> {
-        let parse_multiply = map(tag("*"), |_| Operator::Multiply);
-        let parse_add = map(tag("+"), |_| Operator::Add);
+        map_res(anychar, |c| match c {
+            '+' => Ok(Operator::Add),
+            '-' => Ok(Operator::Subtract),
+            '*' => Ok(Operator::Multiply),
+            '/' => Ok(Operator::Divide),
+            '%' => Ok(Operator::Remainder),
+            _other => Err("Unknown operator"),
+        })(input)
+    }
+
+    fn apply(&self, left: usize, right: usize) -> Result<usize, ArithmeticError> {
+        let result = match self {
+            Operator::Add => left.checked_add(right),
+            Operator::Subtract => left.checked_sub(right),
+            Operator::Multiply => left.checked_mul(right),
+            Operator::Divide | Operator::Remainder if right == 0 => {
+                return Err(ArithmeticError::DivisionByZero);
+            }
+            Operator::Divide => left.checked_div(right),
+            Operator::Remainder => left.checked_rem(right),
+        };
 
-        parse_multiply.or(parse_add).parse(input)
+        result.ok_or(ArithmeticError::OutOfRange {
+            left,
+            operator: *self,
+            right,
+        })
     }
 }
 
-#[derive(Clone, Debug)]
-enum Operand {
-    Num(usize),
-    Old,
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            Operator::Add => '+',
+            Operator::Subtract => '-',
+            Operator::Multiply => '*',
+            Operator::Divide => '/',
+            Operator::Remainder => '%',
+        };
+
+        write!(f, "{symbol}")
+    }
 }
 
-impl Operand {
-    fn parse(input: &str) -> IResult<&str, Self> {
-        let parse_old = map(tag("old"), |_: &str| Operand::Old);
-        let parse_num = map(parse_usize, Operand::Num);
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ArithmeticError {
+    OutOfRange {
+        left: usize,
+        operator: Operator,
+        right: usize,
+    },
+    DivisionByZero,
+}
 
-        parse_old.or(parse_num).parse(input)
+impl fmt::Display for ArithmeticError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArithmeticError::OutOfRange {
+                left,
+                operator,
+                right,
+            } => write!(
+                f,
+                "{left} {operator} {right} is out of range for a worry level"
+            ),
+            ArithmeticError::DivisionByZero => write!(f, "Division by zero"),
+        }
     }
 }
 
@@ -145,47 +259,188 @@ impl FromInput for Day11 {
 
 impl DaySolution for Day11 {
     fn part_one(&self) -> String {
-        let mut monkeys = self.clone();
-        let mut inspection_counts = vec![0; self.0.len()];
-        let worry_relief = |worry: usize| worry / 3;
-
-        for _round in 0..20 {
-            monkeys.run_round(&mut inspection_counts[..], worry_relief);
-        }
+        let inspection_counts = self.run(20, WorryRelief::DivideBy(3), |_round, _throw| {});
 
         calculate_monkey_business(inspection_counts).to_string()
     }
 
     fn part_two(&self) -> String {
-        let mut monkeys = self.clone();
-        let mut inspection_counts = vec![0; self.0.len()];
-        let full_modulo = self.full_modulo();
-        let worry_relief = |worry: usize| worry % full_modulo;
+        let worry_relief = WorryRelief::ModuloBy(self.full_modulo());
+        let inspection_counts = self.extrapolate_inspection_counts(10_000, worry_relief);
+
+        calculate_monkey_business(inspection_counts).to_string()
+    }
 
-        for _round in 0..10_000 {
-            monkeys.run_round(&mut inspection_counts[..], worry_relief);
+    fn tracing(&self) -> Option<&dyn Trace> {
+        Some(self)
+    }
+}
+
+/// How much an item's worry level drops after a monkey inspects it
+/// without damaging it.
+#[derive(Clone, Copy, Debug)]
+enum WorryRelief {
+    DivideBy(usize),
+    ModuloBy(usize),
+}
+
+impl WorryRelief {
+    fn apply(&self, worry: usize) -> usize {
+        match self {
+            WorryRelief::DivideBy(divisor) => worry / divisor,
+            WorryRelief::ModuloBy(modulo) => worry % modulo,
         }
+    }
+}
 
-        calculate_monkey_business(inspection_counts).to_string()
+impl fmt::Display for WorryRelief {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WorryRelief::DivideBy(divisor) => write!(f, "/ {divisor}"),
+            WorryRelief::ModuloBy(modulo) => write!(f, "% {modulo}"),
+        }
     }
 }
 
+/// A single item being inspected by a monkey and thrown to another.
+struct Throw {
+    monkey: usize,
+    worry: usize,
+    operated: usize,
+    relieved: usize,
+    target: usize,
+}
+
 impl Day11 {
+    /// Runs the given number of rounds, calling `on_throw` with the
+    /// 1-based round number for every item thrown, and returns how many
+    /// items each monkey inspected.
+    fn run(
+        &self,
+        rounds: usize,
+        worry_relief: WorryRelief,
+        mut on_throw: impl FnMut(usize, &Throw),
+    ) -> Vec<usize> {
+        let mut monkeys = self.clone();
+        let mut inspection_counts = vec![0; self.0.len()];
+
+        for round in 1..=rounds {
+            monkeys
+                .run_round(&mut inspection_counts[..], worry_relief, |throw| {
+                    on_throw(round, throw)
+                })
+                .unwrap_or_else(|error| panic!("Monkey operation failed: {error}"));
+        }
+
+        inspection_counts
+    }
+
     fn run_round(
         &mut self,
         inspection_counts: &mut [usize],
-        worry_relief: impl Fn(usize) -> usize,
-    ) {
+        worry_relief: WorryRelief,
+        mut on_throw: impl FnMut(&Throw),
+    ) -> Result<(), ArithmeticError> {
         for monkey_index in 0..self.0.len() {
             inspection_counts[monkey_index] += self.0[monkey_index].worries.len();
 
             let operation = self.0[monkey_index].operation.clone();
             for worry in std::mem::take(&mut self.0[monkey_index].worries) {
-                let new_worry = worry_relief(operation.apply(worry));
+                let operated = operation.apply(worry)?;
+                let new_worry = worry_relief.apply(operated);
                 let index_to_move_to = self.0[monkey_index].index_to_move_to(new_worry);
                 self.0[index_to_move_to].worries.push(new_worry);
+
+                on_throw(&Throw {
+                    monkey: monkey_index,
+                    worry,
+                    operated,
+                    relieved: new_worry,
+                    target: index_to_move_to,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Counts how many items each monkey inspects over the given number of
+    /// rounds without simulating every round. Items never affect each
+    /// other, so each one is followed on its own until its holder and
+    /// worry level repeat at the start of a round, and the remaining
+    /// rounds are extrapolated from that cycle.
+    fn extrapolate_inspection_counts(
+        &self,
+        rounds: usize,
+        worry_relief: WorryRelief,
+    ) -> Vec<usize> {
+        let mut inspection_counts = vec![0; self.0.len()];
+
+        for (holder, monkey) in self.0.iter().enumerate() {
+            for worry in &monkey.worries {
+                let item_counts = self.follow_item(holder, *worry, rounds, worry_relief);
+                for (total, count) in inspection_counts.iter_mut().zip(item_counts) {
+                    *total += count;
+                }
+            }
+        }
+
+        inspection_counts
+    }
+
+    /// Counts how many times each monkey inspects a single item over the
+    /// given number of rounds.
+    fn follow_item(
+        &self,
+        mut holder: usize,
+        mut worry: usize,
+        rounds: usize,
+        worry_relief: WorryRelief,
+    ) -> Vec<usize> {
+        let mut first_seen = HashMap::new();
+        // The inspection counts after each number of rounds, starting at zero
+        let mut totals = vec![vec![0; self.0.len()]];
+
+        for round in 0..rounds {
+            if let Some(cycle_start) = first_seen.insert((holder, worry), round) {
+                let cycle_length = round - cycle_start;
+                let cycles = (rounds - cycle_start) / cycle_length;
+                let leftover = (rounds - cycle_start) % cycle_length;
+
+                return (0..self.0.len())
+                    .map(|monkey| {
+                        let before_cycle = totals[cycle_start][monkey];
+                        let per_cycle = totals[round][monkey] - before_cycle;
+                        let leftover_count = totals[cycle_start + leftover][monkey] - before_cycle;
+
+                        before_cycle + cycles * per_cycle + leftover_count
+                    })
+                    .collect();
             }
+
+            // An item thrown to a later monkey gets inspected again in the same round
+            let mut counts = totals[round].clone();
+            loop {
+                let monkey = &self.0[holder];
+                counts[holder] += 1;
+
+                let operated = monkey
+                    .operation
+                    .apply(worry)
+                    .unwrap_or_else(|error| panic!("Monkey operation failed: {error}"));
+                worry = worry_relief.apply(operated);
+
+                let target = monkey.index_to_move_to(worry);
+                let inspected_again = target > holder;
+                holder = target;
+                if !inspected_again {
+                    break;
+                }
+            }
+            totals.push(counts);
         }
+
+        totals.pop().expect("Totals always start with zero rounds")
     }
 
     fn full_modulo(&self) -> usize {
@@ -193,9 +448,224 @@ impl Day11 {
     }
 }
 
-fn calculate_monkey_business(mut inspection_counts: Vec<usize>) -> usize {
+/// Writes every throw of both parts, followed by each monkey's
+/// inspections, throws to every other monkey, and highest worry level
+/// seen after its operation.
+impl Trace for Day11 {
+    fn trace(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let parts = [
+            (1, 20, WorryRelief::DivideBy(3)),
+            (2, 10_000, WorryRelief::ModuloBy(self.full_modulo())),
+        ];
+
+        for (part, rounds, worry_relief) in parts {
+            let mut throw_counts = vec![vec![0; self.0.len()]; self.0.len()];
+            let mut max_worries = vec![0; self.0.len()];
+            let mut written = Ok(());
+
+            let inspection_counts = self.run(rounds, worry_relief, |round, throw| {
+                throw_counts[throw.monkey][throw.target] += 1;
+                max_worries[throw.monkey] = max_worries[throw.monkey].max(throw.operated);
+
+                if written.is_ok() {
+                    written = writeln!(
+                        writer,
+                        r#"{{"event":"throw","part":{part},"round":{round},"monkey":{},"worry":{},"operated":{},"relief":"{worry_relief}","relieved":{},"target":{}}}"#,
+                        throw.monkey, throw.worry, throw.operated, throw.relieved, throw.target
+                    );
+                }
+            });
+            written?;
+
+            for (monkey, inspections) in inspection_counts.iter().enumerate() {
+                let throws = throw_counts[monkey]
+                    .iter()
+                    .map(|count| count.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",");
+
+                writeln!(
+                    writer,
+                    r#"{{"event":"monkey","part":{part},"monkey":{monkey},"inspections":{inspections},"throws":[{throws}],"max_worry":{}}}"#,
+                    max_worries[monkey]
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn calculate_monkey_business(mut inspection_counts: Vec<usize>) -> u128 {
     inspection_counts.sort();
     inspection_counts.reverse();
 
-    inspection_counts.iter().take(2).product()
+    inspection_counts
+        .iter()
+        .take(2)
+        .map(|count| *count as u128)
+        .product()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
+
+Monkey 1:
+  Starting items: 54, 65, 75, 74
+  Operation: new = old + 6
+  Test: divisible by 19
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 79, 60, 97
+  Operation: new = old * old
+  Test: divisible by 13
+    If true: throw to monkey 1
+    If false: throw to monkey 3
+
+Monkey 3:
+  Starting items: 74
+  Operation: new = old + 3
+  Test: divisible by 17
+    If true: throw to monkey 0
+    If false: throw to monkey 1
+";
+
+    fn example() -> Day11 {
+        Day11::from_lines(EXAMPLE.lines().map(str::to_owned))
+    }
+
+    #[test]
+    fn extrapolation_agrees_with_simulation() {
+        let day = example();
+        let worry_relief = WorryRelief::ModuloBy(day.full_modulo());
+
+        for rounds in [0, 1, 2, 3, 7, 20, 137, 1000, 10_000] {
+            assert_eq!(
+                day.extrapolate_inspection_counts(rounds, worry_relief),
+                day.run(rounds, worry_relief, |_round, _throw| {}),
+                "after {rounds} rounds"
+            );
+        }
+    }
+
+    #[test]
+    fn example_monkey_business() {
+        let day = example();
+
+        assert_eq!(day.part_one(), "10605");
+        assert_eq!(day.part_two(), "2713310158");
+    }
+
+    fn evaluate(expression: &str, old: usize) -> Result<usize, ArithmeticError> {
+        let (rest, expression) = Expression::parse(expression).unwrap();
+        assert_eq!(rest, "", "unparsed input");
+
+        expression.evaluate(old)
+    }
+
+    #[test]
+    fn puzzle_operations_still_parse() {
+        let cases = [
+            ("old * 19", 7, 133),
+            ("old + 6", 7, 13),
+            ("old * old", 7, 49),
+            ("old + old", 7, 14),
+            ("old + 3", 0, 3),
+        ];
+
+        for (expression, old, new) in cases {
+            assert_eq!(evaluate(expression, old), Ok(new), "{expression}");
+        }
+    }
+
+    #[test]
+    fn operators_follow_precedence() {
+        assert_eq!(evaluate("old + 2 * 3", 1), Ok(7));
+        assert_eq!(evaluate("2 * 3 + old", 1), Ok(7));
+        assert_eq!(evaluate("old - 4 - 3", 10), Ok(3));
+        assert_eq!(evaluate("old / 2 / 5", 100), Ok(10));
+        assert_eq!(evaluate("old % 7 * 2", 10), Ok(6));
+        assert_eq!(evaluate("old-1*2+old%4", 10), Ok(10));
+    }
+
+    #[test]
+    fn parentheses_group_expressions() {
+        assert_eq!(evaluate("(old + 2) * 3", 1), Ok(9));
+        assert_eq!(evaluate("( old + 2 )*( 3 )", 1), Ok(9));
+        assert_eq!(evaluate("old - (4 - 3)", 10), Ok(9));
+        assert_eq!(evaluate("((old))", 5), Ok(5));
+    }
+
+    #[test]
+    fn subtraction_division_and_remainder() {
+        assert_eq!(evaluate("old - 3", 10), Ok(7));
+        assert_eq!(evaluate("old / 3", 10), Ok(3));
+        assert_eq!(evaluate("old % 3", 10), Ok(1));
+    }
+
+    #[test]
+    fn trailing_input_is_left_unparsed() {
+        let (rest, _expression) = Expression::parse("old * 2 )").unwrap();
+        assert_eq!(rest, " )");
+
+        let (rest, _expression) = Expression::parse("old + 1 plus 2").unwrap();
+        assert_eq!(rest, " plus 2");
+
+        let monkey = EXAMPLE.replacen("new = old * 19", "new = old * 19 + ", 1);
+        assert!(Monkey::parse(&monkey).is_err());
+    }
+
+    #[test]
+    fn malformed_expressions_are_rejected() {
+        for expression in ["", "+ 1", "(old", "new", "* old"] {
+            assert!(Expression::parse(expression).is_err(), "{expression:?}");
+        }
+    }
+
+    #[test]
+    fn arithmetic_errors() {
+        assert_eq!(
+            evaluate("old / 0", 10),
+            Err(ArithmeticError::DivisionByZero)
+        );
+        assert_eq!(
+            evaluate("old % (old - old)", 10),
+            Err(ArithmeticError::DivisionByZero)
+        );
+        assert_eq!(
+            evaluate("old - 11", 10),
+            Err(ArithmeticError::OutOfRange {
+                left: 10,
+                operator: Operator::Subtract,
+                right: 11,
+            })
+        );
+        assert_eq!(
+            evaluate("old * old", usize::MAX),
+            Err(ArithmeticError::OutOfRange {
+                left: usize::MAX,
+                operator: Operator::Multiply,
+                right: usize::MAX,
+            })
+        );
+        assert_eq!(
+            evaluate("1 + old", usize::MAX),
+            Err(ArithmeticError::OutOfRange {
+                left: 1,
+                operator: Operator::Add,
+                right: usize::MAX,
+            })
+        );
+    }
 }
@@ -1,4 +1,6 @@
-use crate::util::{Coordinates, Matrix};
+use crate::image::{Image, Rgb};
+use crate::util::{Coordinates, Direction, Matrix};
+use crate::visualize::{render_rows, Color, Palette, Visualize};
 use crate::{DaySolution, FromInput};
 
 #[derive(Clone)]
@@ -10,41 +12,24 @@ pub struct Day12 {
 
 impl FromInput for Day12 {
     fn from_lines(lines: impl Iterator<Item = String>) -> Self {
-        let mut start = Coordinates::default();
-        let mut end = Coordinates::default();
-
-        let grid = lines
-            .into_iter()
-            .enumerate()
-            .map(|(row_index, line)| {
-                line.chars()
-                    .enumerate()
-                    .map(|(col_index, c)| match c {
-                        'a'..='z' => c as usize - 'a' as usize,
-                        'S' => {
-                            start = Coordinates {
-                                x: col_index as isize,
-                                y: row_index as isize,
-                            };
-                            0
-                        }
-                        'E' => {
-                            end = Coordinates {
-                                x: col_index as isize,
-                                y: row_index as isize,
-                            };
-                            25
-                        }
-                        other => panic!("Unexpected character {other}"),
-                    })
-                    .collect()
+        let (grid, markers) =
+            Matrix::from_lines_with_markers(lines, &['S', 'E'], |_coords, c| match c {
+                'a'..='z' => Ok(c as usize - 'a' as usize),
+                'S' => Ok(0),
+                'E' => Ok(25),
+                _other => Err("Unexpected character"),
             })
-            .collect();
+            .expect("Invalid heightmap");
+
+        let find_marker = |marker: char| match markers.get(&marker).map(Vec::as_slice) {
+            Some([coords]) => *coords,
+            _other => panic!("Must have exactly one {marker} marker"),
+        };
 
         Self {
-            grid: Matrix { items: grid },
-            start,
-            end,
+            grid,
+            start: find_marker('S'),
+            end: find_marker('E'),
         }
     }
 }
@@ -55,22 +40,88 @@ impl DaySolution for Day12 {
     }
 
     fn part_two(&self) -> String {
-        (0..self.grid.size().y)
-            .flat_map(|y| {
-                (0..self.grid.size().x).map(move |x| Coordinates {
-                    x: x as isize,
-                    y: y as isize,
-                })
-            })
-            .filter(|coords| self.grid[*coords] == 0)
-            .map(|coords| dijkstras(&self.grid, coords, self.end))
+        self.grid
+            .enumerate()
+            .filter(|(_coords, height)| **height == 0)
+            .map(|(coords, _height)| dijkstras(&self.grid, coords, self.end))
             .min()
             .expect("No closest coordinates")
             .to_string()
     }
+
+    fn visualization(&self) -> Option<&dyn Visualize> {
+        Some(self)
+    }
+}
+
+/// Draws the heightmap with the shortest path from the start overlaid
+/// as arrows pointing toward the end.
+impl Visualize for Day12 {
+    fn visualize(&self, palette: &Palette) -> String {
+        let steps = self.path_steps();
+
+        render_rows(self.grid.rows().enumerate().map(|(y, row)| {
+            let steps = &steps;
+            row.iter().enumerate().map(move |(x, height)| {
+                let coords = Coordinates::from_indices(x, y).expect("Grid is too large");
+                if coords == self.start {
+                    palette.paint('S', Color::Red)
+                } else if coords == self.end {
+                    palette.paint('E', Color::Red)
+                } else if let Some(direction) = steps[coords] {
+                    let arrow = match direction {
+                        Direction::Up => '^',
+                        Direction::Down => 'v',
+                        Direction::Left => '<',
+                        Direction::Right => '>',
+                    };
+                    palette.paint(arrow, Color::Yellow)
+                } else if palette.is_colored() {
+                    palette.paint((b'a' + *height as u8) as char, Color::Gray)
+                } else {
+                    ".".to_owned()
+                }
+            })
+        }))
+    }
+
+    fn image(&self) -> Option<Image> {
+        let steps = self.path_steps();
+        Some(Image::from_matrix(&self.grid, |(coords, height)| {
+            if coords == self.start || coords == self.end {
+                Rgb::new(220, 50, 50)
+            } else if steps[coords].is_some() {
+                Rgb::new(240, 200, 60)
+            } else {
+                // Shade from dark green valleys to pale peaks
+                let shade = (*height * 160 / 25) as u8;
+                Rgb::new(30 + shade / 2, 70 + shade, 30 + shade / 2)
+            }
+        }))
+    }
+}
+
+impl Day12 {
+    /// The direction of the next step along the shortest path from the
+    /// start, for each square on that path.
+    fn path_steps(&self) -> Matrix<Option<Direction>> {
+        let path = shortest_path(&self.grid, self.start, self.end);
+        let mut steps = Matrix::new(self.grid.size(), || None);
+        for [from, to] in path.array_windows() {
+            steps[*from] = (*to - *from).direction();
+        }
+
+        steps
+    }
 }
 
 fn dijkstras(grid: &Matrix<usize>, start: Coordinates, end: Coordinates) -> usize {
+    shortest_distances(grid, start)[end]
+}
+
+/// Finds the shortest distance from the start to every square on the
+/// grid, leaving `usize::MAX` for squares that can't be reached.
+fn shortest_distances(grid: &Matrix<usize>, start: Coordinates) -> Matrix<usize> {
     let mut visited = Matrix::new(grid.size(), || false);
     let mut distances = Matrix::new(grid.size(), || usize::MAX);
     distances[start] = 0;
@@ -89,7 +140,6 @@ fn dijkstras(grid: &Matrix<usize>, start: Coordinates, end: Coordinates) -> usiz
 
         let neighbors: Vec<(Coordinates, usize)> = grid
             .neighbors(coords)
-            .into_iter()
             .filter(|neighbor| grid[*neighbor] <= grid[coords] + 1 && !visited[*neighbor])
             .map(|neighbor| (neighbor, 1))
             .collect();
@@ -103,5 +153,61 @@ fn dijkstras(grid: &Matrix<usize>, start: Coordinates, end: Coordinates) -> usiz
         }
     }
 
-    distances[end]
+    distances
+}
+
+/// Walks back from the end to the start along decreasing distances,
+/// returning the squares of a shortest path in order.
+fn shortest_path(grid: &Matrix<usize>, start: Coordinates, end: Coordinates) -> Vec<Coordinates> {
+    let distances = shortest_distances(grid, start);
+    if distances[end] == usize::MAX {
+        return vec![];
+    }
+
+    let mut path = vec![end];
+    let mut current = end;
+    while current != start {
+        current = grid
+            .neighbors(current)
+            .find(|previous| {
+                distances[*previous] == distances[current] - 1
+                    && grid[current] <= grid[*previous] + 1
+            })
+            .expect("Path must lead back to the start");
+        path.push(current);
+    }
+
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Day12 {
+        Day12::from_lines(input.lines().map(str::to_owned))
+    }
+
+    #[test]
+    fn example_paths() {
+        let day = parse("Sabqponm\nabcryxxl\naccszExk\nacctuvwj\nabdefghi");
+
+        assert_eq!(day.start, Coordinates { x: 0, y: 0 });
+        assert_eq!(day.end, Coordinates { x: 5, y: 2 });
+        assert_eq!(day.part_one(), "31");
+        assert_eq!(day.part_two(), "29");
+    }
+
+    #[test]
+    #[should_panic(expected = "Must have exactly one S marker")]
+    fn duplicate_start_is_rejected() {
+        parse("SabS\nabcE");
+    }
+
+    #[test]
+    #[should_panic(expected = "Must have exactly one E marker")]
+    fn missing_end_is_rejected() {
+        parse("Sabc\nabcd");
+    }
 }
@@ -1,7 +1,9 @@
 use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
 
 use nom::bytes::complete::tag;
-use nom::character::complete::digit1;
+use nom::character::complete::{digit1, multispace0};
 use nom::combinator::map_res;
 use nom::multi::separated_list0;
 use nom::sequence::delimited;
@@ -11,24 +13,76 @@ use crate::{DaySolution, FromInput};
 
 pub struct Day13(Vec<(Packet, Packet)>);
 
-#[derive(PartialEq, Eq)]
+/// The divider packets inserted into the full list of packets to find
+/// the decoder key.
+const DEFAULT_DIVIDERS: [&str; 2] = ["[[2]]", "[[6]]"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum Packet {
     List(Vec<Packet>),
     Int(usize),
 }
 
 impl Packet {
+    /// Parses a list packet, allowing whitespace (including line breaks)
+    /// around brackets and commas.
     fn parse(input: &str) -> IResult<&str, Self> {
         let parse_int = map_res(digit1, |n: &str| n.parse()).map(Packet::Int);
 
         delimited(
-            tag("["),
-            separated_list0(tag(","), Self::parse.or(parse_int)).map(Self::List),
-            tag("]"),
+            tag("[").and(multispace0),
+            separated_list0(
+                delimited(multispace0, tag(","), multispace0),
+                Self::parse.or(parse_int),
+            )
+            .map(Self::List),
+            multispace0.and(tag("]")),
         )(input)
     }
 }
 
+/// Parses a single packet, ignoring whitespace around it.
+impl FromStr for Packet {
+    type Err = ParsePacketError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Packet::parse(s.trim()) {
+            Ok(("", packet)) => Ok(packet),
+            _other => Err(ParsePacketError(s.to_owned())),
+        }
+    }
+}
+
+/// Writes the packet in the same bracket syntax it's parsed from.
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Packet::Int(n) => write!(f, "{n}"),
+            Packet::List(packets) => {
+                write!(f, "[")?;
+                for (index, packet) in packets.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{packet}")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParsePacketError(String);
+
+impl fmt::Display for ParsePacketError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid packet {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParsePacketError {}
+
 impl Ord for Packet {
     fn cmp(&self, other: &Self) -> Ordering {
         match (self, other) {
@@ -69,21 +123,62 @@ impl PartialOrd for Packet {
 
 impl FromInput for Day13 {
     fn from_lines(lines: impl Iterator<Item = String>) -> Self {
-        Self(
-            lines
-                .chain(Some("".to_owned()))
-                .array_chunks()
-                .map(|[left, right, _empty]| {
-                    let (_rest, left) = Packet::parse(&left).expect("Invalid left packet");
-                    let (_rest, right) = Packet::parse(&right).expect("Invalid right packet");
-
-                    (left, right)
-                })
-                .collect(),
-        )
+        let input = lines.collect::<Vec<String>>().join("\n");
+
+        Self::parse_pairs(&input).unwrap_or_else(|error| panic!("{error}"))
     }
 }
 
+impl Day13 {
+    /// Reads packets from the whole input and pairs them up in order,
+    /// regardless of how they're spread across lines.
+    fn parse_pairs(input: &str) -> Result<Self, ParsePairsError> {
+        let mut packets = vec![];
+        let mut rest = input.trim_start();
+
+        while !rest.is_empty() {
+            let (after_packet, packet) = Packet::parse(rest).map_err(|_error| {
+                let line = input[..input.len() - rest.len()].matches('\n').count() + 1;
+                ParsePairsError::InvalidPacket { line }
+            })?;
+
+            packets.push(packet);
+            rest = after_packet.trim_start();
+        }
+
+        if packets.len() % 2 != 0 {
+            return Err(ParsePairsError::OddPacketCount(packets.len()));
+        }
+
+        let mut packets = packets.into_iter();
+        let mut pairs = vec![];
+        while let (Some(left), Some(right)) = (packets.next(), packets.next()) {
+            pairs.push((left, right));
+        }
+
+        Ok(Self(pairs))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParsePairsError {
+    InvalidPacket { line: usize },
+    OddPacketCount(usize),
+}
+
+impl fmt::Display for ParsePairsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParsePairsError::InvalidPacket { line } => write!(f, "Invalid packet on line {line}"),
+            ParsePairsError::OddPacketCount(count) => {
+                write!(f, "Packets must come in pairs, but found {count}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParsePairsError {}
+
 impl DaySolution for Day13 {
     fn part_one(&self) -> String {
         self.0
@@ -103,29 +198,145 @@ impl DaySolution for Day13 {
     }
 
     fn part_two(&self) -> String {
-        let decoders = [
-            Packet::List(vec![Packet::List(vec![Packet::Int(2)])]),
-            Packet::List(vec![Packet::List(vec![Packet::Int(6)])]),
-        ];
+        self.decoder_key(&DEFAULT_DIVIDERS)
+            .expect("Invalid divider packet")
+            .to_string()
+    }
+}
 
-        let mut packets: Vec<&Packet> = self
-            .0
+impl Day13 {
+    /// Multiplies together the 1-based positions the divider packets would
+    /// have if they were sorted in with all the other packets. Only the
+    /// packets before each divider matter, so nothing is actually sorted,
+    /// and packets that compare equal to a divider are placed after it.
+    fn decoder_key(&self, dividers: &[&str]) -> Result<usize, ParsePacketError> {
+        let dividers = dividers
             .iter()
-            .flat_map(|(left, right)| [left, right])
-            .chain(&decoders)
-            .collect();
-        packets.sort();
+            .map(|divider| divider.parse())
+            .collect::<Result<Vec<Packet>, _>>()?;
 
-        decoders
+        let key = dividers
             .iter()
-            .map(|d| {
-                let index = packets
+            .map(|divider| {
+                let smaller_packets = self
+                    .0
                     .iter()
-                    .position(|p| p == &d)
-                    .expect("Cannot find decoder");
-                index + 1
+                    .flat_map(|(left, right)| [left, right])
+                    .chain(&dividers)
+                    .filter(|packet| *packet < divider)
+                    .count();
+
+                smaller_packets + 1
             })
-            .product::<usize>()
-            .to_string()
+            .product();
+
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+[1,1,3,1,1]
+[1,1,5,1,1]
+
+[[1],[2,3,4]]
+[[1],4]
+
+[9]
+[[8,7,6]]
+
+[[4,4],4,4]
+[[4,4],4,4,4]
+
+[7,7,7,7]
+[7,7,7]
+
+[]
+[3]
+
+[[[]]]
+[[]]
+
+[1,[2,[3,[4,[5,6,7]]]],8,9]
+[1,[2,[3,[4,[5,6,0]]]],8,9]
+";
+
+    #[test]
+    fn packets_round_trip_through_display() {
+        let packets = [
+            "[]",
+            "[[]]",
+            "[[[]]]",
+            "[1,[2,[3,[4,[5,6,7]]]],8,9]",
+            "[10,[234],[[],5678]]",
+        ];
+
+        for packet in packets {
+            let parsed = packet.parse::<Packet>().unwrap();
+            assert_eq!(parsed.to_string(), packet);
+            assert_eq!(parsed.to_string().parse::<Packet>(), Ok(parsed));
+        }
+    }
+
+    #[test]
+    fn packets_ignore_surrounding_whitespace() {
+        assert_eq!(
+            " [1, [ 2 ],\n3]\n".parse::<Packet>(),
+            "[1,[2],3]".parse::<Packet>()
+        );
+    }
+
+    #[test]
+    fn packets_reject_trailing_input() {
+        assert!("[1]]".parse::<Packet>().is_err());
+        assert!("[1] [2]".parse::<Packet>().is_err());
+        assert!("1".parse::<Packet>().is_err());
+    }
+
+    #[test]
+    fn pairs_ignore_line_endings_and_blank_lines() {
+        let expected = Day13::parse_pairs("[1,[2]]\n[3]\n\n[]\n[[4]]").unwrap().0;
+        let inputs = [
+            "[1,[2]]\r\n[3]\r\n\r\n[]\r\n[[4]]\r\n",
+            "\n\n[1,[2]]\n[3]\n\n\n\n[]\n[[4]]\n\n\n",
+            "[1, [2]] [3]\n[ ]\n[ [4] ]",
+            "[1,\n  [2]\n]\n[3]\n\n[]\n[[4]]",
+        ];
+
+        for input in inputs {
+            assert_eq!(Day13::parse_pairs(input).unwrap().0, expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn pairs_report_invalid_packets_by_line() {
+        assert_eq!(
+            Day13::parse_pairs("[1]\n[2]\n\n[3]\n[4,]").err(),
+            Some(ParsePairsError::InvalidPacket { line: 5 })
+        );
+        assert_eq!(
+            Day13::parse_pairs("[1]\r\n\r\nx").err(),
+            Some(ParsePairsError::InvalidPacket { line: 3 })
+        );
+    }
+
+    #[test]
+    fn pairs_need_an_even_packet_count() {
+        assert_eq!(
+            Day13::parse_pairs("[1]\n[2]\n\n[3]\n").err(),
+            Some(ParsePairsError::OddPacketCount(3))
+        );
+        assert_eq!(Day13::parse_pairs("").unwrap().0, []);
+    }
+
+    #[test]
+    fn example_decoder_key() {
+        let day = Day13::parse_pairs(EXAMPLE).unwrap();
+
+        assert_eq!(day.part_one(), "13");
+        assert_eq!(day.decoder_key(&["[[2]]", "[[6]]"]), Ok(140));
     }
 }
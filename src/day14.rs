@@ -1,3 +1,5 @@
+use std::fmt;
+
 use nom::bytes::complete::tag;
 use nom::character::complete::digit1;
 use nom::combinator::{map, map_res};
@@ -5,7 +7,10 @@ use nom::multi::separated_list1;
 use nom::sequence::tuple;
 use nom::IResult;
 
-use crate::util::{BoundingBox, Coordinates, Matrix};
+use crate::animate::Simulation;
+use crate::image::{Image, Rgb};
+use crate::util::{BoundingBox, Coordinates, Direction8, Matrix, SparseGrid};
+use crate::visualize::{render_rows, Color, Palette, Visualize};
 use crate::{DaySolution, FromInput};
 
 pub struct Day14(Vec<Vec<Coordinates>>);
@@ -20,7 +25,9 @@ enum CaveSquare {
 }
 
 struct Cave {
-    grid: Matrix<CaveSquare>,
+    grid: SparseGrid<CaveSquare>,
+    lowest_rock: isize,
+    bottom: CaveBottom,
 }
 
 #[derive(PartialEq, Eq)]
@@ -31,52 +38,60 @@ enum CaveBottom {
 
 impl Cave {
     fn from_lines(lines: &Vec<Vec<Coordinates>>, bottom: CaveBottom) -> Self {
-        let mut bounds = BoundingBox::for_coordinates(lines.iter().flat_map(|line| line));
-        if bottom == CaveBottom::Floor {
-            bounds.bottom_right += Coordinates {
-                x: bounds.bottom_right.y,
-                y: 2,
-            };
-        }
-
-        let mut grid = Matrix::new(bounds.bottom_right, || CaveSquare::Air);
+        let mut grid = SparseGrid::new(CaveSquare::Air);
 
         for line in lines {
             for [left, right] in line.array_windows() {
-                for coords in left.walk_to(*right).unwrap() {
-                    grid[coords] = CaveSquare::Rock;
+                for coords in left.walk_to(*right) {
+                    grid.insert(coords, CaveSquare::Rock);
                 }
             }
         }
 
-        if bottom == CaveBottom::Floor {
-            let floor = vec![CaveSquare::Rock; grid.size().x as usize];
-            let bottom_index = grid.items.len() - 1;
-            grid.items[bottom_index] = floor;
+        let lowest_rock = lines
+            .iter()
+            .flatten()
+            .map(|coords| coords.y)
+            .max()
+            .expect("Cave must have at least one rock");
+
+        Self {
+            grid,
+            lowest_rock,
+            bottom,
         }
+    }
 
-        Self { grid }
+    /// The square at the given coordinates, including the infinitely
+    /// wide floor two below the lowest rock if the cave has one.
+    fn square(&self, coords: Coordinates) -> CaveSquare {
+        if self.bottom == CaveBottom::Floor && coords.y == self.lowest_rock + 2 {
+            CaveSquare::Rock
+        } else {
+            self.grid[coords]
+        }
     }
 
     fn find_next_grain_location(&self) -> Option<Coordinates> {
         let destinations = [
-            Coordinates { x: 0, y: 1 },
-            Coordinates { x: -1, y: 1 },
-            Coordinates { x: 1, y: 1 },
-        ];
+            Direction8::Down,
+            Direction8::DownLeft,
+            Direction8::DownRight,
+        ]
+        .map(|direction| direction.normal_vector());
 
         let mut sand = POUR_LOCATION;
 
-        if self.grid[sand] == CaveSquare::Sand {
+        if self.square(sand) == CaveSquare::Sand {
             return None;
         }
 
         while let Some(destination) = destinations
             .iter()
-            .find(|d| self.grid[sand + **d] == CaveSquare::Air)
+            .find(|d| self.square(sand + **d) == CaveSquare::Air)
         {
             sand += *destination;
-            if sand.x >= self.grid.size().x - 1 || sand.y >= self.grid.size().y - 1 {
+            if self.bottom == CaveBottom::Void && sand.y > self.lowest_rock {
                 return None;
             }
         }
@@ -84,12 +99,50 @@ impl Cave {
         Some(sand)
     }
 
+    fn bounds(&self) -> BoundingBox {
+        self.grid
+            .bounds()
+            .map(|bounds| bounds.including(POUR_LOCATION))
+            .expect("Cave must have rocks")
+    }
+
+    fn render(&self, palette: &Palette) -> String {
+        let bounds = self.bounds();
+
+        render_rows((bounds.top_left.y..bounds.bottom_right.y).map(|y| {
+            (bounds.top_left.x..bounds.bottom_right.x).map(move |x| {
+                let coords = Coordinates { x, y };
+                match self.square(coords) {
+                    _square if coords == POUR_LOCATION => palette.paint('+', Color::Red),
+                    CaveSquare::Rock => palette.paint('#', Color::Gray),
+                    CaveSquare::Sand => palette.paint('o', Color::Yellow),
+                    CaveSquare::Air => ".".to_owned(),
+                }
+            })
+        }))
+    }
+
+    fn image(&self) -> Image {
+        let bounds = self.bounds();
+        let squares = Matrix::from_rows((bounds.top_left.y..bounds.bottom_right.y).map(|y| {
+            (bounds.top_left.x..bounds.bottom_right.x)
+                .map(|x| self.square(Coordinates { x, y }))
+                .collect()
+        }));
+
+        Image::from_matrix(&squares, |(coords, square)| match square {
+            _square if coords + bounds.top_left == POUR_LOCATION => Rgb::new(220, 50, 50),
+            CaveSquare::Rock => Rgb::new(110, 110, 110),
+            CaveSquare::Sand => Rgb::new(230, 190, 80),
+            CaveSquare::Air => Rgb::new(20, 20, 30),
+        })
+    }
+
     fn grain_count(&self) -> usize {
         self.grid
-            .items
             .iter()
-            .map(|row| row.iter().filter(|sq| sq == &&CaveSquare::Sand).count())
-            .sum::<usize>()
+            .filter(|(_coords, sq)| sq == &&CaveSquare::Sand)
+            .count()
     }
 }
 
@@ -109,34 +162,195 @@ fn parse_isize(input: &str) -> IResult<&str, isize> {
 
 impl FromInput for Day14 {
     fn from_lines(lines: impl Iterator<Item = String>) -> Self {
-        Self(
-            lines
-                .map(|line| {
-                    let (_rest, coordinate_list) =
-                        parse_coordinate_list(&line).expect("Invalid coordinate list");
-                    coordinate_list
-                })
-                .collect(),
-        )
+        Self::parse(lines).unwrap_or_else(|error| panic!("{error}"))
+    }
+}
+
+impl Day14 {
+    /// Reads one path of rock per line, checking that every segment is
+    /// horizontal, vertical, or at 45 degrees.
+    fn parse(lines: impl Iterator<Item = String>) -> Result<Self, ParseCaveError> {
+        let mut paths = vec![];
+
+        for (index, line) in lines.enumerate() {
+            let line_number = index + 1;
+            let path = match parse_coordinate_list(&line) {
+                Ok(("", path)) => path,
+                _other => return Err(ParseCaveError::InvalidPath { line_number }),
+            };
+
+            if let Some([from, to]) = path.array_windows().find(|[from, to]| {
+                let delta = *to - *from;
+                delta.x != 0 && delta.y != 0 && delta.x.abs() != delta.y.abs()
+            }) {
+                return Err(ParseCaveError::SlantedSegment {
+                    line_number,
+                    from: *from,
+                    to: *to,
+                });
+            }
+
+            paths.push(path);
+        }
+
+        if paths.is_empty() {
+            return Err(ParseCaveError::NoRocks);
+        }
+
+        Ok(Self(paths))
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseCaveError {
+    InvalidPath {
+        line_number: usize,
+    },
+    SlantedSegment {
+        line_number: usize,
+        from: Coordinates,
+        to: Coordinates,
+    },
+    NoRocks,
+}
+
+impl fmt::Display for ParseCaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseCaveError::InvalidPath { line_number } => {
+                write!(f, "Invalid rock path on line {line_number}")
+            }
+            ParseCaveError::SlantedSegment {
+                line_number,
+                from,
+                to,
+            } => write!(
+                f,
+                "Rock segment from {},{} to {},{} on line {line_number} is not straight or diagonal",
+                from.x, from.y, to.x, to.y
+            ),
+            ParseCaveError::NoRocks => write!(f, "Cave must have at least one rock"),
+        }
+    }
+}
+
+impl std::error::Error for ParseCaveError {}
+
 impl DaySolution for Day14 {
     fn part_one(&self) -> String {
-        let mut cave = Cave::from_lines(&self.0, CaveBottom::Void);
+        self.fill_with_sand(CaveBottom::Void)
+            .grain_count()
+            .to_string()
+    }
+
+    fn part_two(&self) -> String {
+        self.fill_with_sand(CaveBottom::Floor)
+            .grain_count()
+            .to_string()
+    }
+
+    fn visualization(&self) -> Option<&dyn Visualize> {
+        Some(self)
+    }
+
+    fn simulation(&self) -> Option<Box<dyn Simulation + '_>> {
+        let cave = Cave::from_lines(&self.0, CaveBottom::Void);
+        Some(Box::new(SandSimulation { cave }))
+    }
+}
+
+impl Day14 {
+    fn fill_with_sand(&self, bottom: CaveBottom) -> Cave {
+        let mut cave = Cave::from_lines(&self.0, bottom);
         while let Some(coords) = cave.find_next_grain_location() {
-            cave.grid[coords] = CaveSquare::Sand;
+            cave.grid.insert(coords, CaveSquare::Sand);
         }
 
-        cave.grain_count().to_string()
+        cave
     }
+}
 
-    fn part_two(&self) -> String {
-        let mut cave = Cave::from_lines(&self.0, CaveBottom::Floor);
-        while let Some(coords) = cave.find_next_grain_location() {
-            cave.grid[coords] = CaveSquare::Sand;
+/// Draws the sand that settles on the rocks before it starts
+/// flowing into the abyss.
+impl Visualize for Day14 {
+    fn visualize(&self, palette: &Palette) -> String {
+        self.fill_with_sand(CaveBottom::Void).render(palette)
+    }
+
+    fn image(&self) -> Option<Image> {
+        Some(self.fill_with_sand(CaveBottom::Void).image())
+    }
+}
+
+/// Drops one grain of sand per step until sand flows into the abyss.
+struct SandSimulation {
+    cave: Cave,
+}
+
+impl Simulation for SandSimulation {
+    fn step(&mut self) -> bool {
+        match self.cave.find_next_grain_location() {
+            Some(coords) => {
+                self.cave.grid.insert(coords, CaveSquare::Sand);
+                true
+            }
+            None => false,
         }
+    }
+
+    fn render(&self, palette: &Palette) -> String {
+        self.cave.render(palette)
+    }
+
+    fn image(&self) -> Option<Image> {
+        Some(self.cave.image())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<Day14, ParseCaveError> {
+        Day14::parse(input.lines().map(str::to_owned))
+    }
+
+    #[test]
+    fn example_sand() {
+        let day = parse("498,4 -> 498,6 -> 496,6\n503,4 -> 502,4 -> 502,9 -> 494,9").unwrap();
+
+        assert_eq!(day.part_one(), "24");
+        assert_eq!(day.part_two(), "93");
+    }
+
+    #[test]
+    fn diagonal_segments_are_allowed() {
+        let day = parse("498,4 -> 500,6 -> 502,4").unwrap();
+
+        assert_eq!(
+            day.0[0],
+            [(498, 4), (500, 6), (502, 4)].map(Coordinates::from)
+        );
+    }
+
+    #[test]
+    fn slanted_segments_are_rejected() {
+        assert_eq!(
+            parse("498,4 -> 498,6\n498,6 -> 500,7").err(),
+            Some(ParseCaveError::SlantedSegment {
+                line_number: 2,
+                from: Coordinates { x: 498, y: 6 },
+                to: Coordinates { x: 500, y: 7 },
+            })
+        );
+    }
 
-        cave.grain_count().to_string()
+    #[test]
+    fn invalid_paths_are_rejected() {
+        assert_eq!(
+            parse("498,4 -> 498,6 ->").err(),
+            Some(ParseCaveError::InvalidPath { line_number: 1 })
+        );
+        assert_eq!(parse("").err(), Some(ParseCaveError::NoRocks));
     }
 }
@@ -1,66 +1,30 @@
 use std::collections::HashSet;
-use std::ops::Add;
 
 use nom::bytes::complete::tag;
 use nom::character::complete::digit1;
 use nom::combinator::map_res;
 use nom::IResult;
 
+use crate::util::{BoundingBox3, Coordinates3, Grid3};
+use crate::visualize::{render_rows, Color, Palette, Visualize};
 use crate::{DaySolution, FromInput};
 
-#[derive(Clone)]
-pub struct Day18(HashSet<Cube>);
-
-#[derive(Clone, Copy, Hash, PartialEq, Eq)]
-struct Cube {
-    x: isize,
-    y: isize,
-    z: isize,
-}
-
-impl Cube {
-    fn parse(input: &str) -> IResult<&str, Self> {
-        let (input, x) = parse_isize(input)?;
-        let (input, _) = tag(",")(input)?;
-        let (input, y) = parse_isize(input)?;
-        let (input, _) = tag(",")(input)?;
-        let (input, z) = parse_isize(input)?;
-
-        Ok((input, Self { x, y, z }))
-    }
-
-    fn all_directions() -> [Cube; 6] {
-        [
-            Cube::from((0, 0, 1)),
-            Cube::from((0, 0, -1)),
-            Cube::from((0, 1, 0)),
-            Cube::from((0, -1, 0)),
-            Cube::from((1, 0, 0)),
-            Cube::from((-1, 0, 0)),
-        ]
-    }
-
-    fn all_neighbors(&self) -> [Cube; 6] {
-        Self::all_directions().map(|delta| *self + delta)
-    }
+pub struct Day18 {
+    cubes: Vec<Coordinates3>,
+    lava: CubeSet,
 }
 
-impl From<(isize, isize, isize)> for Cube {
-    fn from((x, y, z): (isize, isize, isize)) -> Self {
-        Self { x, y, z }
-    }
-}
+/// The largest volume we're willing to allocate a dense bit grid for.
+const MAX_DENSE_VOLUME: usize = 1 << 24;
 
-impl Add for Cube {
-    type Output = Self;
+fn parse_cube(input: &str) -> IResult<&str, Coordinates3> {
+    let (input, x) = parse_isize(input)?;
+    let (input, _) = tag(",")(input)?;
+    let (input, y) = parse_isize(input)?;
+    let (input, _) = tag(",")(input)?;
+    let (input, z) = parse_isize(input)?;
 
-    fn add(self, rhs: Cube) -> Self::Output {
-        Self {
-            x: self.x + rhs.x,
-            y: self.y + rhs.y,
-            z: self.z + rhs.z,
-        }
-    }
+    Ok((input, Coordinates3 { x, y, z }))
 }
 
 fn parse_isize(input: &str) -> IResult<&str, isize> {
@@ -69,14 +33,21 @@ fn parse_isize(input: &str) -> IResult<&str, isize> {
 
 impl FromInput for Day18 {
     fn from_lines(lines: impl Iterator<Item = String>) -> Self {
-        Self(
-            lines
-                .map(|line| {
-                    let (_rest, cube) = Cube::parse(&line).expect("Invalid cube");
-                    cube
-                })
-                .collect(),
-        )
+        let parsed: Vec<Coordinates3> = lines
+            .map(|line| {
+                let (_rest, cube) = parse_cube(&line).expect("Invalid cube");
+                cube
+            })
+            .collect();
+
+        // Repeated cubes are only counted once
+        let mut lava = CubeSet::with_bounds(BoundingBox3::for_coordinates(parsed.iter()));
+        let cubes = parsed
+            .into_iter()
+            .filter(|cube| lava.insert(*cube))
+            .collect();
+
+        Self { cubes, lava }
     }
 }
 
@@ -86,106 +57,236 @@ impl DaySolution for Day18 {
     }
 
     fn part_two(&self) -> String {
-        let mut lava = self.clone();
-        lava.fill_in_gaps();
+        let (_exterior, faces) = self.flood_exterior();
+        faces.to_string()
+    }
 
-        lava.surface_area().to_string()
+    fn visualization(&self) -> Option<&dyn Visualize> {
+        Some(self)
     }
 }
 
-#[derive(Debug)]
-struct CubeBounds {
-    min_x: isize,
-    max_x: isize,
-    min_y: isize,
-    max_y: isize,
-    min_z: isize,
-    max_z: isize,
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Voxel {
+    Lava,
+    Exterior,
+    Pocket,
+}
+
+/// Draws the droplet one z-slice at a time, showing lava as `#`, air
+/// outside the droplet as `.`, and air pockets trapped inside as `o`.
+impl Visualize for Day18 {
+    fn visualize(&self, palette: &Palette) -> String {
+        let bounds = BoundingBox3::for_coordinates(self.cubes.iter());
+        let (exterior, _faces) = self.flood_exterior();
+
+        let mut voxels = Grid3::new(bounds, || Voxel::Pocket);
+        for z in bounds.start.z..bounds.end.z {
+            for y in bounds.start.y..bounds.end.y {
+                for x in bounds.start.x..bounds.end.x {
+                    let coords = Coordinates3 { x, y, z };
+                    if self.lava.contains(&coords) {
+                        voxels[coords] = Voxel::Lava;
+                    } else if exterior.contains(&coords) {
+                        voxels[coords] = Voxel::Exterior;
+                    }
+                }
+            }
+        }
+
+        (bounds.start.z..bounds.end.z)
+            .map(|z| {
+                let slice = render_rows((bounds.start.y..bounds.end.y).map(|y| {
+                    let voxels = &voxels;
+                    (bounds.start.x..bounds.end.x).map(move |x| {
+                        match voxels[Coordinates3 { x, y, z }] {
+                            Voxel::Lava => palette.paint('#', Color::Red),
+                            Voxel::Exterior => palette.paint('.', Color::Gray),
+                            Voxel::Pocket => palette.paint('o', Color::Cyan),
+                        }
+                    })
+                }));
+
+                format!("z = {z}\n{slice}")
+            })
+            .collect::<Vec<String>>()
+            .join("\n\n")
+    }
 }
 
 impl Day18 {
     fn surface_area(&self) -> usize {
-        self.0
+        self.cubes
             .iter()
             .map(|cube| {
-                cube.all_neighbors()
+                cube.face_neighbors()
                     .into_iter()
-                    .filter(|neighbor| !self.0.contains(neighbor))
+                    .filter(|neighbor| !self.lava.contains(neighbor))
                     .count()
             })
             .sum()
     }
 
-    fn bounds(&self) -> CubeBounds {
-        self.0.iter().fold(
-            CubeBounds {
-                min_x: isize::MAX,
-                max_x: isize::MIN,
-                min_y: isize::MAX,
-                max_y: isize::MIN,
-                min_z: isize::MAX,
-                max_z: isize::MIN,
-            },
-            |bounds, cube| CubeBounds {
-                min_x: std::cmp::min(bounds.min_x, cube.x),
-                max_x: std::cmp::max(bounds.max_x, cube.x),
-                min_y: std::cmp::min(bounds.min_y, cube.y),
-                max_y: std::cmp::max(bounds.max_y, cube.y),
-                min_z: std::cmp::min(bounds.min_z, cube.z),
-                max_z: std::cmp::max(bounds.max_z, cube.z),
-            },
-        )
-    }
+    /// Flood fills the air around the droplet from the corner of a bounding
+    /// box padded by one, returning the air reached and the number of lava
+    /// faces it touches.
+    fn flood_exterior(&self) -> (CubeSet, usize) {
+        if self.cubes.is_empty() {
+            return (CubeSet::Sparse(HashSet::new()), 0);
+        }
 
-    fn fill_in_gaps(&mut self) {
-        let bounds = self.bounds();
+        let bounds = BoundingBox3::for_coordinates(self.cubes.iter()).padded(1);
+        let start = bounds.start;
 
-        for x in bounds.min_x..=bounds.max_x {
-            for y in bounds.min_y..=bounds.max_y {
-                for z in bounds.min_z..=bounds.max_z {
-                    let cube = Cube { x, y, z };
-                    if self.0.contains(&cube) {
-                        continue;
-                    }
+        let mut visited = CubeSet::with_bounds(bounds);
+        visited.insert(start);
+        let mut queue = vec![start];
+        let mut faces = 0;
 
-                    if !self.is_a_way_out(&bounds, &cube) {
-                        self.0.insert(cube);
-                    }
+        while let Some(air) = queue.pop() {
+            for neighbor in air.face_neighbors() {
+                if !bounds.contains(neighbor) {
+                    continue;
+                }
+
+                if self.lava.contains(&neighbor) {
+                    faces += 1;
+                } else if visited.insert(neighbor) {
+                    queue.push(neighbor);
                 }
             }
         }
+
+        (visited, faces)
     }
+}
 
-    fn is_a_way_out(&self, bounds: &CubeBounds, cube: &Cube) -> bool {
-        let mut queue = vec![*cube];
-        let mut visited: HashSet<Cube> = HashSet::new();
+/// A set of cubes, stored as a dense bit grid when its bounds are small
+/// enough and as a hash set otherwise.
+enum CubeSet {
+    Dense {
+        bounds: BoundingBox3,
+        bits: Vec<u64>,
+    },
+    Sparse(HashSet<Coordinates3>),
+}
 
-        while let Some(c) = queue.pop() {
-            if !bounds.contains(&c) {
-                return true;
-            } else {
-                visited.insert(c);
-            }
+impl CubeSet {
+    fn with_bounds(bounds: BoundingBox3) -> Self {
+        match bounds.volume() {
+            Some(volume) if volume <= MAX_DENSE_VOLUME => CubeSet::Dense {
+                bounds,
+                bits: vec![0; volume.div_ceil(64)],
+            },
+            _other => CubeSet::Sparse(HashSet::new()),
+        }
+    }
 
-            queue.extend(
-                Cube::all_directions()
-                    .into_iter()
-                    .map(|direction| c + direction)
-                    .filter(|neighbor| !self.0.contains(neighbor) && !visited.contains(neighbor)),
-            );
+    /// Adds the cube to the set, returning whether it wasn't already present.
+    fn insert(&mut self, cube: Coordinates3) -> bool {
+        match self {
+            CubeSet::Dense { bounds, bits } => {
+                let index = bounds
+                    .index_of(cube)
+                    .expect("Cube is outside of set bounds");
+                let mask = 1 << (index % 64);
+                let was_present = bits[index / 64] & mask != 0;
+                bits[index / 64] |= mask;
+
+                !was_present
+            }
+            CubeSet::Sparse(cubes) => cubes.insert(cube),
         }
+    }
 
-        false
+    fn contains(&self, cube: &Coordinates3) -> bool {
+        match self {
+            CubeSet::Dense { bounds, bits } => bounds
+                .index_of(*cube)
+                .map(|index| bits[index / 64] & (1 << (index % 64)) != 0)
+                .unwrap_or(false),
+            CubeSet::Sparse(cubes) => cubes.contains(cube),
+        }
     }
 }
 
-impl CubeBounds {
-    fn contains(&self, cube: &Cube) -> bool {
-        self.min_x <= cube.x
-            && cube.x <= self.max_x
-            && self.min_y <= cube.y
-            && cube.y <= self.max_y
-            && self.min_z <= cube.z
-            && cube.z <= self.max_z
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+2,2,2
+1,2,2
+3,2,2
+2,1,2
+2,3,2
+2,2,1
+2,2,3
+2,2,4
+2,2,6
+1,2,5
+3,2,5
+2,1,5
+2,3,5
+";
+
+    fn parse(input: &str) -> Day18 {
+        Day18::from_lines(input.lines().map(str::to_owned))
+    }
+
+    #[test]
+    fn example_surface_areas() {
+        let day = parse(EXAMPLE);
+
+        assert_eq!(day.part_one(), "64");
+        assert_eq!(day.part_two(), "58");
+    }
+
+    #[test]
+    fn single_cube() {
+        let day = parse("1,1,1");
+
+        assert_eq!(day.part_one(), "6");
+        assert_eq!(day.part_two(), "6");
+    }
+
+    #[test]
+    fn repeated_cubes_count_once() {
+        let day = parse("1,1,1\n1,1,1\n2,1,1\n1,1,1");
+
+        assert_eq!(day.cubes.len(), 2);
+        assert_eq!(day.part_one(), "10");
+        assert_eq!(day.part_two(), "10");
+    }
+
+    #[test]
+    fn empty_droplet() {
+        let day = parse("");
+
+        assert_eq!(day.part_one(), "0");
+        assert_eq!(day.part_two(), "0");
+        assert_eq!(day.visualize(&Palette::new(false)), "");
+    }
+
+    #[test]
+    fn dense_and_sparse_sets_agree() {
+        let day = parse(EXAMPLE);
+        let bounds = BoundingBox3::for_coordinates(day.cubes.iter()).padded(1);
+        let mut dense = CubeSet::with_bounds(bounds);
+        let mut sparse = CubeSet::Sparse(HashSet::new());
+        assert!(matches!(dense, CubeSet::Dense { .. }));
+
+        for cube in day.cubes.iter().chain(&day.cubes) {
+            assert_eq!(dense.insert(*cube), sparse.insert(*cube));
+        }
+
+        for z in -2..9 {
+            for y in -1..5 {
+                for x in -1..5 {
+                    let coords = Coordinates3 { x, y, z };
+                    assert_eq!(dense.contains(&coords), sparse.contains(&coords));
+                }
+            }
+        }
     }
 }
@@ -1,12 +1,60 @@
-use std::collections::HashSet;
+use std::fmt;
 
 use crate::{DaySolution, FromInput};
 
-pub struct Day3(Vec<Vec<char>>);
+pub struct Day3(Vec<Rucksack>);
+
+struct Rucksack {
+    left: ItemSet,
+    right: ItemSet,
+}
+
+impl Rucksack {
+    fn items(&self) -> ItemSet {
+        ItemSet(self.left.0 | self.right.0)
+    }
+}
+
+/// A set of item types, with one bit per item priority.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ItemSet(u64);
+
+impl ItemSet {
+    const ALL: ItemSet = ItemSet((1 << 52) - 1);
+
+    fn from_items(items: &str) -> Self {
+        Self(
+            items
+                .chars()
+                .map(|item| 1 << (item_priority(item) - 1))
+                .fold(0, |set, bit| set | bit),
+        )
+    }
+
+    fn intersection(self, other: ItemSet) -> ItemSet {
+        ItemSet(self.0 & other.0)
+    }
+
+    fn items(self) -> impl Iterator<Item = char> {
+        (1..=52)
+            .filter(move |priority| self.0 & (1 << (priority - 1)) != 0)
+            .map(item_for_priority)
+    }
+}
 
 impl FromInput for Day3 {
     fn from_lines(lines: impl Iterator<Item = String>) -> Self {
-        Self(lines.map(|line| line.chars().collect()).collect())
+        Self(
+            lines
+                .map(|line| {
+                    let (left, right) = line.split_at(line.len() / 2);
+                    Rucksack {
+                        left: ItemSet::from_items(left),
+                        right: ItemSet::from_items(right),
+                    }
+                })
+                .collect(),
+        )
     }
 }
 
@@ -14,9 +62,10 @@ impl DaySolution for Day3 {
     fn part_one(&self) -> String {
         self.0
             .iter()
-            .map(|sack| {
-                let (left_half, right_half) = sack.split_at(sack.len() / 2);
-                find_common_item(&[left_half, right_half])
+            .enumerate()
+            .map(|(index, sack)| {
+                find_common_item(&[sack.left, sack.right])
+                    .unwrap_or_else(|error| panic!("Rucksack {}: {error}", index + 1))
             })
             .map(item_priority)
             .sum::<usize>()
@@ -24,12 +73,31 @@ impl DaySolution for Day3 {
     }
 
     fn part_two(&self) -> String {
+        self.sum_badge_priorities(3).to_string()
+    }
+}
+
+impl Day3 {
+    /// Splits the elves into groups of the given size, in order, and adds
+    /// up the priorities of the one item type each group has in common.
+    fn sum_badge_priorities(&self, group_size: usize) -> usize {
+        assert!(group_size > 0, "Groups must have at least one elf");
+        assert!(
+            self.0.len().is_multiple_of(group_size),
+            "{} elves can't be split into groups of {group_size}",
+            self.0.len()
+        );
+
         self.0
-            .array_chunks()
-            .map(|[elf_1, elf_2, elf_3]| find_common_item(&[elf_1, elf_2, elf_3]))
+            .chunks(group_size)
+            .enumerate()
+            .map(|(index, group)| {
+                let items: Vec<ItemSet> = group.iter().map(Rucksack::items).collect();
+                find_common_item(&items)
+                    .unwrap_or_else(|error| panic!("Group {}: {error}", index + 1))
+            })
             .map(item_priority)
-            .sum::<usize>()
-            .to_string()
+            .sum()
     }
 }
 
@@ -41,12 +109,106 @@ fn item_priority(item: char) -> usize {
     }
 }
 
-fn find_common_item(groups: &[&[char]]) -> char {
-    let mut all_items: HashSet<&char> = HashSet::from_iter(groups[0].iter());
+fn item_for_priority(priority: usize) -> char {
+    match priority {
+        1..=26 => (b'a' + (priority - 1) as u8) as char,
+        27..=52 => (b'A' + (priority - 27) as u8) as char,
+        _ => panic!("Invalid priority provided"),
+    }
+}
+
+fn find_common_item(sets: &[ItemSet]) -> Result<char, CommonItemError> {
+    let common = sets
+        .iter()
+        .fold(ItemSet::ALL, |common, set| common.intersection(*set));
 
-    for group in groups.iter().skip(1) {
-        all_items.drain_filter(|i| !group.contains(i));
+    match common.0.count_ones() {
+        0 => Err(CommonItemError::NoCommonItem),
+        1 => Ok(common.items().next().expect("Set has one item")),
+        _more => Err(CommonItemError::MultipleCommonItems(
+            common.items().collect(),
+        )),
     }
+}
 
-    **all_items.iter().next().expect("Must have a common item")
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CommonItemError {
+    NoCommonItem,
+    MultipleCommonItems(String),
+}
+
+impl fmt::Display for CommonItemError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommonItemError::NoCommonItem => write!(f, "No item is common to all of them"),
+            CommonItemError::MultipleCommonItems(items) => {
+                write!(f, "Expected one common item, but found {items:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CommonItemError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(rucksacks: &[&str]) -> Day3 {
+        Day3::from_lines(rucksacks.iter().map(|sack| sack.to_string()))
+    }
+
+    #[test]
+    fn example_priorities() {
+        let day = parse(&[
+            "vJrwpWtwJgWrhcsFMMfFFhFp",
+            "jqHRNqRjqzjGDLGLrsFMfFZSrLrFZsSL",
+            "PmmdzqPrVvPwwTWBwg",
+            "wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn",
+            "ttgJtRGJQctTZtZT",
+            "CrZsJsPPZsGzwwsLwLmpwMDw",
+        ]);
+
+        assert_eq!(day.part_one(), "157");
+        assert_eq!(day.part_two(), "70");
+    }
+
+    #[test]
+    fn badges_for_other_group_sizes() {
+        assert_eq!(parse(&["aa", "BB"]).sum_badge_priorities(1), 1 + 28);
+        assert_eq!(
+            parse(&["abXY", "aCDE", "ZzqQ", "Zrst"]).sum_badge_priorities(2),
+            1 + 52
+        );
+        assert_eq!(
+            parse(&["ka", "kb", "ck", "dk", "kK", "Lk"]).sum_badge_priorities(6),
+            11
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "5 elves can't be split into groups of 2")]
+    fn uneven_groups_panic() {
+        parse(&["aa", "aa", "aa", "aa", "aa"]).sum_badge_priorities(2);
+    }
+
+    #[test]
+    fn common_item_errors() {
+        let sets = |items: &[&str]| -> Vec<ItemSet> {
+            items
+                .iter()
+                .map(|items| ItemSet::from_items(items))
+                .collect()
+        };
+
+        assert_eq!(find_common_item(&sets(&["abZ", "Zcd"])), Ok('Z'));
+        assert_eq!(
+            find_common_item(&sets(&["ab", "cd"])),
+            Err(CommonItemError::NoCommonItem)
+        );
+        assert_eq!(
+            find_common_item(&sets(&["abcD", "bDce", "Dxcb"])),
+            Err(CommonItemError::MultipleCommonItems("bcD".to_owned()))
+        );
+    }
 }
@@ -1,32 +1,220 @@
-use nom::{
-    bytes::complete::tag,
-    character::complete::{anychar, digit1},
-    combinator::{map, map_res},
-    multi::separated_list1,
-    sequence::delimited,
-    IResult, Parser,
-};
+use std::fmt;
+use std::str::FromStr;
 
+use nom::bytes::complete::tag;
+use nom::character::complete::digit1;
+use nom::combinator::map_res;
+use nom::IResult;
+
+use crate::visualize::{Palette, Visualize};
 use crate::{DaySolution, FromInput};
 
 pub struct Day5 {
-    crate_stacks: Vec<Vec<char>>,
+    crate_stacks: CrateStacks,
     orders: Vec<MoveOrder>,
 }
 
+/// The crates in each stack, listed from the bottom up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct CrateStacks(Vec<Vec<char>>);
+
+impl CrateStacks {
+    /// Has the crane carry out a single order, failing if the order names
+    /// a stack that doesn't exist or asks for more crates than it has.
+    fn apply(&mut self, crane: &dyn Crane, order: &MoveOrder) -> Result<(), MoveError> {
+        for stack in [order.from, order.to] {
+            if stack == 0 || stack > self.0.len() {
+                return Err(MoveError::UnknownStack(stack));
+            }
+        }
+
+        let from = &mut self.0[order.from - 1];
+        if from.len() < order.amount {
+            return Err(MoveError::NotEnoughCrates {
+                stack: order.from,
+                available: from.len(),
+            });
+        }
+
+        let lifted = from.split_off(from.len() - order.amount);
+        crane.set_down(lifted, &mut self.0[order.to - 1]);
+
+        Ok(())
+    }
+
+    /// The crate on top of each stack, or a space for empty stacks.
+    fn top_crates(&self) -> String {
+        self.0
+            .iter()
+            .map(|stack| stack.last().copied().unwrap_or(' '))
+            .collect()
+    }
+}
+
+/// Reads a drawing of the stacks in the puzzle input format, with one
+/// row of crates per line and the stack numbers on the last line.
+impl FromStr for CrateStacks {
+    type Err = ParseStacksError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines: Vec<&str> = s.lines().collect();
+        let numbering = lines.pop().ok_or(ParseStacksError::MissingNumbering)?;
+
+        let stack_count = numbering.split_whitespace().count();
+        let numbered_in_order = numbering
+            .split_whitespace()
+            .enumerate()
+            .all(|(index, number)| number.parse() == Ok(index + 1));
+        if stack_count == 0 || !numbered_in_order {
+            return Err(ParseStacksError::InvalidNumbering(numbering.to_owned()));
+        }
+
+        let mut stacks = vec![vec![]; stack_count];
+        for (row, line) in lines.iter().enumerate().rev() {
+            let chars: Vec<char> = line.chars().collect();
+            if chars.len() > stack_count * 4 {
+                return Err(ParseStacksError::InvalidCell {
+                    row: row + 1,
+                    stack: stack_count + 1,
+                });
+            }
+
+            // Each cell is three characters wide, followed by a space
+            for (index, stack) in stacks.iter_mut().enumerate() {
+                let cell: Vec<char> = chars.iter().skip(index * 4).take(4).copied().collect();
+
+                match cell.as_slice() {
+                    ['[', crate_, ']'] | ['[', crate_, ']', ' '] => {
+                        if stack.len() != lines.len() - row - 1 {
+                            return Err(ParseStacksError::FloatingCrate { stack: index + 1 });
+                        }
+                        stack.push(*crate_);
+                    }
+                    cell if cell.iter().all(|c| *c == ' ') => {}
+                    _other => {
+                        return Err(ParseStacksError::InvalidCell {
+                            row: row + 1,
+                            stack: index + 1,
+                        })
+                    }
+                }
+            }
+        }
+
+        Ok(Self(stacks))
+    }
+}
+
+/// Draws the stacks in the same format as the puzzle input, with the
+/// stack numbers underneath.
+impl fmt::Display for CrateStacks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let height = self.0.iter().map(Vec::len).max().unwrap_or(0);
+
+        for level in (0..height).rev() {
+            let row = self
+                .0
+                .iter()
+                .map(|stack| match stack.get(level) {
+                    Some(crate_) => format!("[{crate_}]"),
+                    None => "   ".to_owned(),
+                })
+                .collect::<Vec<String>>()
+                .join(" ");
+            writeln!(f, "{row}")?;
+        }
+
+        let numbers = (1..=self.0.len())
+            .map(|number| format!("{number:^3}"))
+            .collect::<Vec<String>>()
+            .join(" ");
+        write!(f, "{numbers}")
+    }
+}
+
+/// A crane that moves crates from the top of one stack to another.
+trait Crane {
+    /// Puts the crates lifted off the top of a stack, listed from the
+    /// bottom up, onto the destination stack.
+    fn set_down(&self, lifted: Vec<char>, onto: &mut Vec<char>);
+}
+
+/// Moves crates one at a time, so they end up in reverse order.
+struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn set_down(&self, lifted: Vec<char>, onto: &mut Vec<char>) {
+        onto.extend(lifted.into_iter().rev());
+    }
+}
+
+/// Moves all the crates at once, keeping their order.
+struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn set_down(&self, lifted: Vec<char>, onto: &mut Vec<char>) {
+        onto.extend(lifted);
+    }
+}
+
 pub struct MoveOrder {
     amount: usize,
     from: usize,
     to: usize,
 }
 
-fn parse_crate_row(input: &str) -> IResult<&str, Vec<Option<char>>> {
-    separated_list1(
-        tag(" "),
-        map(delimited(tag("["), anychar, tag("]")), Some).or(map(tag("   "), |_t: &str| None)),
-    )(input)
+impl fmt::Display for MoveOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.amount, self.from, self.to)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MoveError {
+    UnknownStack(usize),
+    NotEnoughCrates { stack: usize, available: usize },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::UnknownStack(stack) => write!(f, "There is no stack {stack}"),
+            MoveError::NotEnoughCrates { stack, available } => {
+                write!(f, "Stack {stack} only has {available} crates")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseStacksError {
+    MissingNumbering,
+    InvalidNumbering(String),
+    InvalidCell { row: usize, stack: usize },
+    FloatingCrate { stack: usize },
+}
+
+impl fmt::Display for ParseStacksError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseStacksError::MissingNumbering => write!(f, "Missing the stack numbers"),
+            ParseStacksError::InvalidNumbering(line) => {
+                write!(f, "Stack numbers {line:?} must count up from 1")
+            }
+            ParseStacksError::InvalidCell { row, stack } => {
+                write!(f, "Row {row} has an invalid cell for stack {stack}")
+            }
+            ParseStacksError::FloatingCrate { stack } => {
+                write!(f, "Stack {stack} has a crate floating above an empty space")
+            }
+        }
+    }
 }
 
+impl std::error::Error for ParseStacksError {}
+
 fn parse_order(input: &str) -> IResult<&str, MoveOrder> {
     let (input, _) = tag("move ")(input)?;
     let (input, amount) = parse_usize(input)?;
@@ -44,24 +232,18 @@ fn parse_usize(input: &str) -> IResult<&str, usize> {
 
 impl FromInput for Day5 {
     fn from_lines(mut lines: impl Iterator<Item = String>) -> Self {
-        let mut crate_stacks = vec![];
-
-        while let Ok((_rest, crate_row)) = parse_crate_row(&lines.next().unwrap()) {
-            if crate_stacks.is_empty() {
-                crate_stacks = std::iter::repeat(vec![]).take(crate_row.len()).collect();
-            }
-
-            for (index, crate_) in crate_row.into_iter().enumerate() {
-                if let Some(crate_) = crate_ {
-                    crate_stacks[index].push(crate_);
-                }
-            }
-        }
+        let drawing = lines
+            .by_ref()
+            .take_while(|line| !line.is_empty())
+            .collect::<Vec<String>>()
+            .join("\n");
+        let crate_stacks = drawing
+            .parse()
+            .unwrap_or_else(|error| panic!("Invalid crate drawing: {error}"));
 
         let orders = lines
-            .skip(1)
             .map(|line| {
-                let (_rest, order) = parse_order(&line).unwrap();
+                let (_rest, order) = parse_order(&line).expect("Invalid move order");
                 order
             })
             .collect();
@@ -75,29 +257,157 @@ impl FromInput for Day5 {
 
 impl DaySolution for Day5 {
     fn part_one(&self) -> String {
+        self.rearrange(&CrateMover9000).top_crates()
+    }
+
+    fn part_two(&self) -> String {
+        self.rearrange(&CrateMover9001).top_crates()
+    }
+
+    fn visualization(&self) -> Option<&dyn Visualize> {
+        Some(self)
+    }
+}
+
+impl Day5 {
+    fn rearrange(&self, crane: &dyn Crane) -> CrateStacks {
         let mut stacks = self.crate_stacks.clone();
 
-        for order in &self.orders {
-            for _ in 0..order.amount {
-                let popped = stacks[order.from - 1].remove(0);
-                stacks[order.to - 1].insert(0, popped);
-            }
+        for (index, order) in self.orders.iter().enumerate() {
+            stacks
+                .apply(crane, order)
+                .unwrap_or_else(|error| panic!("Move {} ({order}) failed: {error}", index + 1));
         }
 
-        stacks.iter().map(|stack| stack[0]).collect()
+        stacks
     }
+}
 
-    fn part_two(&self) -> String {
-        let mut stacks = self.crate_stacks.clone();
+/// Draws the starting stacks and the stacks each crane leaves behind.
+impl Visualize for Day5 {
+    fn visualize(&self, _palette: &Palette) -> String {
+        format!(
+            "Starting stacks:\n{}\n\nAfter the CrateMover 9000:\n{}\n\nAfter the CrateMover 9001:\n{}",
+            self.crate_stacks,
+            self.rearrange(&CrateMover9000),
+            self.rearrange(&CrateMover9001)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "    [D]    \n\
+                           [N] [C]    \n\
+                           [Z] [M] [P]\n \
+                            1   2   3 ";
 
-        for order in &self.orders {
-            let mut moving_stack: Vec<char> =
-                stacks[order.from - 1].drain(0..order.amount).collect();
-            moving_stack.append(&mut stacks[order.to - 1]);
+    #[test]
+    fn example_round_trips() {
+        let stacks = EXAMPLE.parse::<CrateStacks>().unwrap();
 
-            stacks[order.to - 1] = moving_stack;
+        assert_eq!(stacks.0, [vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']]);
+        assert_eq!(stacks.to_string(), EXAMPLE);
+    }
+
+    #[test]
+    fn more_than_nine_stacks_round_trip() {
+        let drawing = [
+            "                                [J]    ",
+            "[A] [B]     [D] [E] [F] [G] [H] [I] [K]",
+            " 1   2   3   4   5   6   7   8   9  10 ",
+        ]
+        .join("\n");
+
+        let stacks = drawing.parse::<CrateStacks>().unwrap();
+
+        assert_eq!(stacks.0.len(), 10);
+        assert_eq!(stacks.0[2], []);
+        assert_eq!(stacks.0[8], ['I', 'J']);
+        assert_eq!(stacks.to_string(), drawing);
+    }
+
+    #[test]
+    fn crates_must_rest_on_something() {
+        let drawing = "[A] [B]\n    [C]\n 1   2 ";
+
+        assert_eq!(
+            drawing.parse::<CrateStacks>(),
+            Err(ParseStacksError::FloatingCrate { stack: 1 })
+        );
+    }
+
+    #[test]
+    fn stacks_must_be_numbered_from_one() {
+        for numbering in [" 1   3 ", " 0   1 ", "[A]"] {
+            assert_eq!(
+                format!("[A] [B]\n{numbering}").parse::<CrateStacks>(),
+                Err(ParseStacksError::InvalidNumbering(numbering.to_owned())),
+                "numbering {numbering:?}"
+            );
+        }
+    }
+
+    fn order(amount: usize, from: usize, to: usize) -> MoveOrder {
+        MoveOrder { amount, from, to }
+    }
+
+    fn rearranged(crane: &dyn Crane) -> CrateStacks {
+        let mut stacks = EXAMPLE.parse::<CrateStacks>().unwrap();
+        for order in [
+            order(1, 2, 1),
+            order(3, 1, 3),
+            order(2, 2, 1),
+            order(1, 1, 2),
+        ] {
+            stacks.apply(crane, &order).unwrap();
         }
 
-        stacks.iter().map(|stack| stack[0]).collect()
+        stacks
+    }
+
+    #[test]
+    fn crane_9000_moves_one_crate_at_a_time() {
+        let stacks = rearranged(&CrateMover9000);
+
+        assert_eq!(stacks.0, [vec!['C'], vec!['M'], vec!['P', 'D', 'N', 'Z']]);
+        assert_eq!(stacks.top_crates(), "CMZ");
+    }
+
+    #[test]
+    fn crane_9001_moves_crates_together() {
+        let stacks = rearranged(&CrateMover9001);
+
+        assert_eq!(stacks.0, [vec!['M'], vec!['C'], vec!['P', 'Z', 'N', 'D']]);
+        assert_eq!(stacks.top_crates(), "MCD");
+    }
+
+    #[test]
+    fn orders_must_name_existing_stacks() {
+        let mut stacks = EXAMPLE.parse::<CrateStacks>().unwrap();
+
+        for (order, stack) in [(order(1, 0, 1), 0), (order(1, 1, 4), 4)] {
+            assert_eq!(
+                stacks.apply(&CrateMover9000, &order),
+                Err(MoveError::UnknownStack(stack))
+            );
+        }
+        assert_eq!(stacks, EXAMPLE.parse().unwrap());
+    }
+
+    #[test]
+    fn orders_cant_take_more_crates_than_a_stack_has() {
+        let mut stacks = EXAMPLE.parse::<CrateStacks>().unwrap();
+
+        assert_eq!(
+            stacks.apply(&CrateMover9001, &order(2, 3, 1)),
+            Err(MoveError::NotEnoughCrates {
+                stack: 3,
+                available: 1
+            })
+        );
+        assert_eq!(stacks, EXAMPLE.parse().unwrap());
     }
 }
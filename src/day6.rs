@@ -1,40 +1,162 @@
+use std::io::{self, BufReader, Read, Write};
+
+use crate::trace::Trace;
 use crate::{DaySolution, FromInput};
 
-pub struct Day6(Vec<char>);
+pub struct Day6(Vec<u8>);
 
 impl FromInput for Day6 {
     fn from_lines(mut lines: impl Iterator<Item = String>) -> Self {
-        Self(
-            lines
-                .next()
-                .expect("Must have a single line")
-                .chars()
-                .collect(),
-        )
+        Self(lines.next().expect("Must have a single line").into_bytes())
     }
 }
 
 impl DaySolution for Day6 {
     fn part_one(&self) -> String {
-        find_start_marker::<4>(&self.0[..]).to_string()
+        find_markers(&self.0, 4)
+            .next()
+            .expect("Must have packet start marker")
+            .to_string()
     }
 
     fn part_two(&self) -> String {
-        find_start_marker::<14>(&self.0[..]).to_string()
+        find_markers(&self.0, 14)
+            .next()
+            .expect("Must have message start marker")
+            .to_string()
+    }
+
+    fn tracing(&self) -> Option<&dyn Trace> {
+        Some(self)
     }
 }
 
-fn find_start_marker<const N: usize>(chars: &[char]) -> usize {
-    chars
-        .array_windows::<N>()
-        .enumerate()
-        .find_map(|(index, chars)| chars_are_all_unique(chars).then(|| index + N))
-        .expect("Must have packet start marker")
+/// Writes every packet and message marker in the signal, not only the first.
+impl Trace for Day6 {
+    fn trace(&self, writer: &mut dyn Write) -> io::Result<()> {
+        for window in [4, 14] {
+            for marker in stream_markers(&self.0[..], window) {
+                writeln!(writer, r#"{{"window":{window},"marker":{}}}"#, marker?)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Tracks a signal one byte at a time, spotting the points where the
+/// last `window` bytes are all different.
+struct MarkerDetector {
+    window: usize,
+    /// The position each byte value was last seen at
+    last_seen: [Option<usize>; 256],
+    /// The start of the longest run of different bytes ending at the latest byte
+    run_start: usize,
+    position: usize,
+}
+
+impl MarkerDetector {
+    fn new(window: usize) -> Self {
+        assert!(window > 0, "Marker window must be at least one byte");
+
+        Self {
+            window,
+            last_seen: [None; 256],
+            run_start: 0,
+            position: 0,
+        }
+    }
+
+    /// Takes the next byte of the signal, returning the number of bytes
+    /// read so far if they end in a marker.
+    fn push(&mut self, byte: u8) -> Option<usize> {
+        if let Some(last_seen) = self.last_seen[byte as usize] {
+            self.run_start = self.run_start.max(last_seen + 1);
+        }
+        self.last_seen[byte as usize] = Some(self.position);
+        self.position += 1;
+
+        (self.position - self.run_start >= self.window).then_some(self.position)
+    }
+}
+
+/// Finds the position just after every marker in the signal.
+fn find_markers(signal: &[u8], window: usize) -> impl Iterator<Item = usize> + '_ {
+    let mut detector = MarkerDetector::new(window);
+
+    signal.iter().filter_map(move |byte| detector.push(*byte))
 }
 
-fn chars_are_all_unique(chars: &[char]) -> bool {
-    !chars
-        .iter()
-        .enumerate()
-        .any(|(index, c)| chars.iter().skip(index + 1).any(|c_2| c == c_2))
+/// Finds markers like [`find_markers`], reading the signal as it goes
+/// instead of holding all of it in memory. The signal ends at the first
+/// line break, like the single line of the puzzle input.
+fn stream_markers(signal: impl Read, window: usize) -> impl Iterator<Item = io::Result<usize>> {
+    let mut detector = MarkerDetector::new(window);
+
+    BufReader::new(signal)
+        .bytes()
+        .take_while(|byte| !matches!(byte, Ok(b'\n' | b'\r')))
+        .filter_map(move |byte| match byte {
+            Ok(byte) => detector.push(byte).map(Ok),
+            Err(error) => Some(Err(error)),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLES: [(&str, usize, usize); 5] = [
+        ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 7, 19),
+        ("bvwbjplbgvbhsrlpgdmjqwftvncz", 5, 23),
+        ("nppdvjthqldpwncqszvftbrmjlhg", 6, 23),
+        ("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 10, 29),
+        ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 11, 26),
+    ];
+
+    #[test]
+    fn first_markers_of_the_examples() {
+        for (signal, packet, message) in EXAMPLES {
+            assert_eq!(find_markers(signal.as_bytes(), 4).next(), Some(packet));
+            assert_eq!(find_markers(signal.as_bytes(), 14).next(), Some(message));
+        }
+    }
+
+    #[test]
+    fn every_marker_is_found() {
+        assert_eq!(find_markers(b"aabcab", 3).collect::<Vec<_>>(), [4, 5, 6]);
+        assert_eq!(find_markers(b"aab", 1).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(find_markers(b"abc", 4).next(), None);
+    }
+
+    #[test]
+    fn streaming_matches_the_in_memory_search() {
+        for (signal, _packet, _message) in EXAMPLES {
+            for window in [1, 4, 14] {
+                let streamed = stream_markers(signal.as_bytes(), window)
+                    .collect::<io::Result<Vec<usize>>>()
+                    .unwrap();
+
+                assert_eq!(
+                    streamed,
+                    find_markers(signal.as_bytes(), window).collect::<Vec<_>>()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn streaming_stops_at_the_end_of_the_line() {
+        let streamed = stream_markers(&b"abcd\nefgh"[..], 2)
+            .collect::<io::Result<Vec<usize>>>()
+            .unwrap();
+
+        assert_eq!(streamed, [2, 3, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marker window must be at least one byte")]
+    fn empty_window_is_rejected() {
+        find_markers(b"abc", 0).next();
+    }
 }
@@ -1,137 +1,437 @@
-use std::iter::Extend;
+use std::fmt;
 
+use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::{alpha1, digit1, space0};
-use nom::combinator::map_res;
+use nom::character::complete::digit1;
+use nom::combinator::{map, map_res, rest};
+use nom::sequence::preceded;
 use nom::{IResult, Parser};
 
+use crate::visualize::{Color, Palette, Visualize};
 use crate::{DaySolution, FromInput};
 
-pub struct Day7(Vec<Command>);
+pub struct Day7(FileSystem);
 
+/// A command from the shell transcript, along with the output it
+/// printed and the line numbers of that output.
 #[derive(Clone, Debug)]
-struct Command {
-    name: String,
-    argument: String,
-    files: Vec<FileType>,
+enum Command {
+    Cd(String),
+    Ls(Vec<(usize, FileType)>),
+    Pwd(Option<(usize, String)>),
+    Mkdir(String),
+    Rm(String),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 enum FileType {
-    File(File),
-    Dir(Dir),
-}
-
-#[derive(Clone, Debug)]
-struct File {
-    name: String,
-    size: usize,
-}
-
-#[derive(Clone, Debug)]
-struct Dir {
-    name: String,
-    files: Vec<FileType>,
+    File { name: String, size: usize },
+    Dir { name: String },
 }
 
 impl FileType {
     fn name(&self) -> &str {
         match self {
-            FileType::File(file) => &file.name,
-            FileType::Dir(dir) => &dir.name,
+            FileType::File { name, .. } => name,
+            FileType::Dir { name } => name,
         }
     }
 }
 
-impl Dir {
-    fn add_file(&mut self, file: FileType) {
-        if !self.files.iter().any(|f| f.name() == file.name()) {
-            self.files.push(file);
-        } else {
-            println!("{:?}, {:?}", file, &self.files);
+type NodeId = usize;
+
+/// A directory tree stored as a flat list of nodes, where every node
+/// links back to its parent directory.
+struct FileSystem {
+    nodes: Vec<Node>,
+}
+
+struct Node {
+    name: String,
+    parent: Option<NodeId>,
+    /// The size of a file, or the total size of everything inside a directory
+    size: usize,
+    children: Option<Vec<NodeId>>,
+}
+
+impl FileSystem {
+    const ROOT: NodeId = 0;
+
+    fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                name: "/".to_owned(),
+                parent: None,
+                size: 0,
+                children: Some(vec![]),
+            }],
         }
     }
 
-    fn reconstruct_from_commands(commands: &[Command]) -> Self {
-        let mut current_path = vec![];
-        let mut root = Dir {
-            name: "/".to_owned(),
-            files: vec![],
-        };
-
-        for command in commands {
-            if &command.name == "cd" {
-                if &command.argument == ".." {
-                    current_path.pop();
-                } else if &command.argument == "/" {
-                    current_path.clear();
-                } else {
-                    current_path.push(command.argument.clone());
+    /// Replays the commands of a transcript, checking that each one makes
+    /// sense for the filesystem built up so far.
+    fn from_transcript(commands: &[(usize, Command)]) -> Result<Self, TranscriptError> {
+        let mut file_system = Self::new();
+        let mut current_dir = Self::ROOT;
+
+        for (line, command) in commands {
+            let line = *line;
+            match command {
+                Command::Cd(argument) if argument == "/" => current_dir = Self::ROOT,
+                Command::Cd(argument) if argument == ".." => {
+                    current_dir = file_system.nodes[current_dir]
+                        .parent
+                        .ok_or(TranscriptError::AboveRoot { line })?;
+                }
+                Command::Cd(argument) => {
+                    current_dir = file_system
+                        .child(current_dir, argument)
+                        .filter(|child| file_system.is_dir(*child))
+                        .ok_or_else(|| TranscriptError::NoSuchDirectory {
+                            line,
+                            path: file_system.child_path(current_dir, argument),
+                        })?;
+                }
+                Command::Ls(files) => {
+                    for (line, file) in files {
+                        file_system.add_file(current_dir, file, *line)?;
+                    }
+                }
+                Command::Pwd(Some((line, printed))) => {
+                    let path = file_system.path(current_dir);
+                    if printed != &path {
+                        return Err(TranscriptError::WrongWorkingDirectory {
+                            line: *line,
+                            expected: path,
+                            found: printed.clone(),
+                        });
+                    }
                 }
-            } else if &command.name == "ls" {
-                let dir = root.find(&current_path);
-                for file in &command.files {
-                    dir.add_file(file.clone());
+                Command::Pwd(None) => {}
+                Command::Mkdir(name) => {
+                    if file_system.child(current_dir, name).is_some() {
+                        return Err(TranscriptError::AlreadyExists {
+                            line,
+                            path: file_system.child_path(current_dir, name),
+                        });
+                    }
+
+                    let dir = FileType::Dir { name: name.clone() };
+                    file_system.add_file(current_dir, &dir, line)?;
+                }
+                Command::Rm(name) => {
+                    let child = file_system.child(current_dir, name).ok_or_else(|| {
+                        TranscriptError::NoSuchFile {
+                            line,
+                            path: file_system.child_path(current_dir, name),
+                        }
+                    })?;
+                    file_system.remove(child);
                 }
             }
         }
 
-        root
+        Ok(file_system)
+    }
+
+    fn child(&self, dir: NodeId, name: &str) -> Option<NodeId> {
+        self.nodes[dir]
+            .children
+            .iter()
+            .flatten()
+            .find(|child| self.nodes[**child].name == name)
+            .copied()
     }
 
-    fn find(&mut self, path: &[String]) -> &mut Self {
-        match path {
-            [] => self,
-            [first, rest @ ..] => {
-                let child_dir = self
-                    .files
-                    .iter_mut()
-                    .find(|child| child.name() == first)
-                    .expect("No child found for path given");
+    /// Adds a listed file to the directory. Listing the same file again is
+    /// fine, as long as it hasn't changed size or type.
+    fn add_file(
+        &mut self,
+        dir: NodeId,
+        file: &FileType,
+        line: usize,
+    ) -> Result<(), TranscriptError> {
+        let (size, children) = match file {
+            FileType::File { size, .. } => (*size, None),
+            FileType::Dir { .. } => (0, Some(vec![])),
+        };
 
-                match child_dir {
-                    FileType::Dir(child) => child.find(rest),
-                    FileType::File(_file) => panic!("Found file instead of directory"),
+        if let Some(existing) = self.child(dir, file.name()) {
+            let unchanged = match file {
+                FileType::File { size, .. } => {
+                    !self.is_dir(existing) && self.nodes[existing].size == *size
                 }
-            }
+                FileType::Dir { .. } => self.is_dir(existing),
+            };
+
+            return if unchanged {
+                Ok(())
+            } else {
+                Err(TranscriptError::ConflictingListing {
+                    line,
+                    path: self.path(existing),
+                })
+            };
         }
+
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            name: file.name().to_owned(),
+            parent: Some(dir),
+            size,
+            children,
+        });
+        self.nodes[dir]
+            .children
+            .as_mut()
+            .expect("Files can only be added to directories")
+            .push(id);
+        self.add_to_ancestor_sizes(dir, size as isize);
+
+        Ok(())
     }
 
-    fn size(&self) -> usize {
-        self.files
-            .iter()
-            .map(|file| match file {
-                FileType::File(file) => file.size,
-                FileType::Dir(dir) => dir.size(),
-            })
-            .sum()
+    /// Unlinks a file or a whole directory from its parent. The nodes stay
+    /// in the arena, but can no longer be reached from the root.
+    fn remove(&mut self, id: NodeId) {
+        let parent = self.nodes[id].parent.expect("Can't remove the root");
+        if let Some(siblings) = &mut self.nodes[parent].children {
+            siblings.retain(|sibling| *sibling != id);
+        }
+
+        self.add_to_ancestor_sizes(parent, -(self.nodes[id].size as isize));
     }
 
-    fn all_sizes(&self) -> Vec<(&str, usize)> {
-        self.files
-            .iter()
-            .flat_map(|file| match file {
-                FileType::Dir(dir) => dir.all_sizes(),
-                FileType::File(_file) => vec![],
+    fn add_to_ancestor_sizes(&mut self, dir: NodeId, change: isize) {
+        let mut ancestor = Some(dir);
+        while let Some(ancestor_id) = ancestor {
+            self.nodes[ancestor_id].size = self.nodes[ancestor_id]
+                .size
+                .checked_add_signed(change)
+                .expect("Directory size must not be negative");
+            ancestor = self.nodes[ancestor_id].parent;
+        }
+    }
+
+    fn is_dir(&self, id: NodeId) -> bool {
+        self.nodes[id].children.is_some()
+    }
+
+    fn size(&self, id: NodeId) -> usize {
+        self.nodes[id].size
+    }
+
+    /// The full path from the root, such as `/a/e`.
+    fn path(&self, id: NodeId) -> String {
+        let mut names = vec![];
+        let mut current = id;
+        while let Some(parent) = self.nodes[current].parent {
+            names.push(self.nodes[current].name.as_str());
+            current = parent;
+        }
+        names.reverse();
+
+        format!("/{}", names.join("/"))
+    }
+
+    /// The full path a child with the given name would have.
+    fn child_path(&self, dir: NodeId, name: &str) -> String {
+        match dir {
+            Self::ROOT => format!("/{name}"),
+            _other => format!("{}/{name}", self.path(dir)),
+        }
+    }
+
+    /// Every directory that can be reached from the root, parents first.
+    fn directories(&self) -> impl Iterator<Item = NodeId> + '_ {
+        let mut stack = vec![Self::ROOT];
+
+        std::iter::from_fn(move || {
+            let dir = stack.pop()?;
+            let children = self.nodes[dir].children.iter().flatten();
+            stack.extend(children.rev().filter(|child| self.is_dir(**child)));
+
+            Some(dir)
+        })
+    }
+
+    /// Lists every file and directory indented beneath its parent, like `tree`.
+    fn render_tree(&self, palette: &Palette) -> String {
+        let mut lines = vec![];
+        self.render_subtree(Self::ROOT, "", "", palette, &mut lines);
+
+        lines.join("\n")
+    }
+
+    fn render_subtree(
+        &self,
+        id: NodeId,
+        branch: &str,
+        indent: &str,
+        palette: &Palette,
+        lines: &mut Vec<String>,
+    ) {
+        let node = &self.nodes[id];
+        let name = if self.is_dir(id) {
+            palette.paint(&node.name, Color::Cyan)
+        } else {
+            node.name.clone()
+        };
+        lines.push(format!("{branch}{name} ({})", node.size));
+
+        let children = node.children.as_deref().unwrap_or_default();
+        for (index, child) in children.iter().enumerate() {
+            let (branch, child_indent) = if index == children.len() - 1 {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+
+            let branch = format!("{indent}{branch}");
+            let indent = format!("{indent}{child_indent}");
+            self.render_subtree(*child, &branch, &indent, palette, lines);
+        }
+    }
+
+    /// Lists every directory's total size and full path from largest
+    /// to smallest, like `du -h`.
+    fn render_du(&self) -> String {
+        let mut directories: Vec<NodeId> = self.directories().collect();
+        directories.sort_by_key(|id| (std::cmp::Reverse(self.size(*id)), self.path(*id)));
+
+        directories
+            .into_iter()
+            .map(|id| {
+                format!(
+                    "{:>5}\t{}",
+                    human_readable_size(self.size(id)),
+                    self.path(id)
+                )
             })
-            .chain(Some((self.name.as_str(), self.size())))
-            .collect()
+            .collect::<Vec<String>>()
+            .join("\n")
     }
 }
 
-fn parse_command(input: &str) -> IResult<&str, Command> {
-    let (input, _) = tag("$ ")(input)?;
-    let (input, name) = alpha1(input)?;
-    let (rest, _) = space0(input)?;
+/// Formats a size with a binary unit suffix, keeping one decimal place
+/// for small values.
+fn human_readable_size(size: usize) -> String {
+    const UNITS: [&str; 5] = ["", "K", "M", "G", "T"];
+
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        size.to_string()
+    } else if value < 10.0 {
+        format!("{value:.1}{}", UNITS[unit])
+    } else {
+        format!("{value:.0}{}", UNITS[unit])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TranscriptError {
+    UnknownCommand {
+        line: usize,
+        text: String,
+    },
+    InvalidListing {
+        line: usize,
+        text: String,
+    },
+    UnexpectedOutput {
+        line: usize,
+        text: String,
+    },
+    NoSuchDirectory {
+        line: usize,
+        path: String,
+    },
+    NoSuchFile {
+        line: usize,
+        path: String,
+    },
+    AboveRoot {
+        line: usize,
+    },
+    ConflictingListing {
+        line: usize,
+        path: String,
+    },
+    AlreadyExists {
+        line: usize,
+        path: String,
+    },
+    WrongWorkingDirectory {
+        line: usize,
+        expected: String,
+        found: String,
+    },
+}
+
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TranscriptError::UnknownCommand { line, text } => {
+                write!(f, "Line {line}: Unknown command {text:?}")
+            }
+            TranscriptError::InvalidListing { line, text } => {
+                write!(f, "Line {line}: Invalid ls output {text:?}")
+            }
+            TranscriptError::UnexpectedOutput { line, text } => {
+                write!(f, "Line {line}: Unexpected output {text:?}")
+            }
+            TranscriptError::NoSuchDirectory { line, path } => {
+                write!(
+                    f,
+                    "Line {line}: Can't cd into {path}, which isn't a known directory"
+                )
+            }
+            TranscriptError::NoSuchFile { line, path } => {
+                write!(f, "Line {line}: Can't remove {path}, which doesn't exist")
+            }
+            TranscriptError::AboveRoot { line } => {
+                write!(f, "Line {line}: Can't cd above the root directory")
+            }
+            TranscriptError::ConflictingListing { line, path } => write!(
+                f,
+                "Line {line}: Listing of {path} doesn't match an earlier listing"
+            ),
+            TranscriptError::AlreadyExists { line, path } => {
+                write!(f, "Line {line}: Can't create {path}, which already exists")
+            }
+            TranscriptError::WrongWorkingDirectory {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Line {line}: pwd printed {found}, but the working directory is {expected}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TranscriptError {}
 
-    Ok((
-        "",
-        Command {
-            name: name.to_owned(),
-            argument: rest.to_owned(),
-            files: vec![],
-        },
-    ))
+fn parse_command(input: &str) -> IResult<&str, Command> {
+    let argument = |command| preceded(tag(command), rest.map(str::to_owned));
+
+    preceded(
+        tag("$ "),
+        alt((
+            map(argument("cd "), Command::Cd),
+            map(argument("mkdir "), Command::Mkdir),
+            map(argument("rm "), Command::Rm),
+            map(tag("ls"), |_| Command::Ls(vec![])),
+            map(tag("pwd"), |_| Command::Pwd(None)),
+        )),
+    )(input)
 }
 
 fn parse_file(input: &str) -> IResult<&str, FileType> {
@@ -140,10 +440,9 @@ fn parse_file(input: &str) -> IResult<&str, FileType> {
 
         Ok((
             "",
-            FileType::Dir(Dir {
+            FileType::Dir {
                 name: rest.to_owned(),
-                files: vec![],
-            }),
+            },
         ))
     }
 
@@ -153,74 +452,309 @@ fn parse_file(input: &str) -> IResult<&str, FileType> {
 
         Ok((
             "",
-            FileType::File(File {
-                size,
+            FileType::File {
                 name: rest.to_owned(),
-            }),
+                size,
+            },
         ))
     }
 
     dir_parser.or(file_parser).parse(input)
 }
 
-impl FromInput for Day7 {
-    fn from_lines(lines: impl Iterator<Item = String>) -> Self {
-        let mut commands = vec![];
-        let mut current_command: Option<Command> = None;
-
-        for line in lines {
-            if line.starts_with('$') {
-                commands.extend(current_command.take());
-                let (_rest, current) = parse_command(&line).expect("Invalid command");
-                current_command = Some(current);
-                continue;
-            }
+/// Splits a transcript into commands, each paired with the 1-based line
+/// number it was typed on and holding its parsed output.
+fn parse_transcript(
+    lines: impl Iterator<Item = String>,
+) -> Result<Vec<(usize, Command)>, TranscriptError> {
+    let mut commands: Vec<(usize, Command)> = vec![];
+
+    for (index, text) in lines.enumerate() {
+        let line = index + 1;
+
+        if text.starts_with('$') {
+            let command = match parse_command(&text) {
+                Ok(("", command)) => command,
+                _other => return Err(TranscriptError::UnknownCommand { line, text }),
+            };
+            commands.push((line, command));
+            continue;
+        }
 
-            if let Some(current) = &mut current_command {
-                let (_rest, file) = parse_file(&line).expect("Invalid file");
-                current.files.push(file);
-            }
+        match commands.last_mut() {
+            Some((_line, Command::Ls(files))) => match parse_file(&text) {
+                Ok((_rest, file)) => files.push((line, file)),
+                Err(_error) => return Err(TranscriptError::InvalidListing { line, text }),
+            },
+            Some((_line, Command::Pwd(printed @ None))) => *printed = Some((line, text)),
+            _other => return Err(TranscriptError::UnexpectedOutput { line, text }),
         }
+    }
 
-        commands.extend(current_command.take());
+    Ok(commands)
+}
 
-        Self(commands)
+impl FromInput for Day7 {
+    fn from_lines(lines: impl Iterator<Item = String>) -> Self {
+        let file_system = parse_transcript(lines)
+            .and_then(|commands| FileSystem::from_transcript(&commands))
+            .unwrap_or_else(|error| panic!("Invalid transcript: {error}"));
+
+        Self(file_system)
     }
 }
 
 impl DaySolution for Day7 {
     fn part_one(&self) -> String {
-        let directory = Dir::reconstruct_from_commands(&self.0[..]);
-        let directory_sizes = directory.all_sizes();
+        let file_system = &self.0;
 
-        directory_sizes
-            .into_iter()
-            .filter(|(name, size)| *size <= 100_000 && name != &"/")
-            .map(|(_name, size)| size)
+        file_system
+            .directories()
+            .filter(|id| *id != FileSystem::ROOT)
+            .map(|id| file_system.size(id))
+            .filter(|size| *size <= 100_000)
             .sum::<usize>()
             .to_string()
     }
 
     fn part_two(&self) -> String {
-        let directory = Dir::reconstruct_from_commands(&self.0[..]);
-        let mut directory_sizes = directory.all_sizes();
-        directory_sizes.sort_by_key(|(_name, size)| *size);
+        let chosen = self.directory_to_delete();
+        format!("{} ({})", self.0.size(chosen), self.0.path(chosen))
+    }
 
-        let total_size = directory_sizes
-            .iter()
-            .find(|(name, _size)| name == &"/")
-            .map(|(_name, size)| size)
-            .expect("Couldn't find root directory");
+    fn visualization(&self) -> Option<&dyn Visualize> {
+        Some(self)
+    }
+}
+
+impl Day7 {
+    /// The smallest directory that frees up enough space for the update.
+    fn directory_to_delete(&self) -> NodeId {
+        let file_system = &self.0;
+        let total_size = file_system.size(FileSystem::ROOT);
 
         let filesystem_size = 70_000_000;
         let space_needed = 30_000_000;
         let size_to_delete = space_needed - (filesystem_size - total_size);
 
-        directory_sizes
-            .into_iter()
-            .map(|(_name, size)| size)
-            .find(|size| *size >= size_to_delete)
+        file_system
+            .directories()
+            .filter(|id| file_system.size(*id) >= size_to_delete)
+            .min_by_key(|id| file_system.size(*id))
             .expect("No sufficiently large directory found")
-            .to_string()
+    }
+}
+
+/// Draws the reconstructed directory tree, followed by the size of
+/// every directory and the one chosen for deletion.
+impl Visualize for Day7 {
+    fn visualize(&self, palette: &Palette) -> String {
+        let chosen = self.directory_to_delete();
+
+        format!(
+            "{}\n\n{}\n\nDirectory to delete: {} ({})",
+            self.0.render_tree(palette),
+            self.0.render_du(),
+            self.0.path(chosen),
+            self.0.size(chosen)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k
+";
+
+    fn replay(transcript: &str) -> Result<FileSystem, TranscriptError> {
+        let commands = parse_transcript(transcript.lines().map(str::to_owned))?;
+        FileSystem::from_transcript(&commands)
+    }
+
+    fn directory_size(file_system: &FileSystem, path: &str) -> Option<usize> {
+        file_system
+            .directories()
+            .find(|id| file_system.path(*id) == path)
+            .map(|id| file_system.size(id))
+    }
+
+    #[test]
+    fn example_directory_sizes() {
+        let file_system = replay(EXAMPLE).unwrap();
+
+        assert_eq!(directory_size(&file_system, "/"), Some(48381165));
+        assert_eq!(directory_size(&file_system, "/a"), Some(94853));
+        assert_eq!(directory_size(&file_system, "/a/e"), Some(584));
+        assert_eq!(directory_size(&file_system, "/d"), Some(24933642));
+
+        let day = Day7(file_system);
+        assert_eq!(day.part_one(), "95437");
+        assert_eq!(day.part_two(), "24933642 (/d)");
+    }
+
+    #[test]
+    fn mkdir_and_rm_update_ancestor_sizes() {
+        let created = "\
+$ mkdir a
+$ cd a
+$ mkdir b
+$ cd b
+$ ls
+100 x
+200 y
+$ pwd
+/a/b
+$ cd /
+$ ls
+50 z";
+        let file_system = replay(created).unwrap();
+        assert_eq!(directory_size(&file_system, "/"), Some(350));
+        assert_eq!(directory_size(&file_system, "/a"), Some(300));
+        assert_eq!(directory_size(&file_system, "/a/b"), Some(300));
+
+        let removed_dir = format!("{created}\n$ cd a\n$ rm b");
+        let file_system = replay(&removed_dir).unwrap();
+        assert_eq!(directory_size(&file_system, "/"), Some(50));
+        assert_eq!(directory_size(&file_system, "/a"), Some(0));
+        assert_eq!(directory_size(&file_system, "/a/b"), None);
+
+        let removed_file = format!("{removed_dir}\n$ cd ..\n$ rm z");
+        let file_system = replay(&removed_file).unwrap();
+        assert_eq!(directory_size(&file_system, "/"), Some(0));
+    }
+
+    #[test]
+    fn replay_errors_report_their_line() {
+        let cases = [
+            ("$ cd /\n$ cd ..", TranscriptError::AboveRoot { line: 2 }),
+            (
+                "$ ls\n100 a\n$ cd a",
+                TranscriptError::NoSuchDirectory {
+                    line: 3,
+                    path: "/a".to_owned(),
+                },
+            ),
+            (
+                "$ mkdir a\n$ cd a\n$ cd b",
+                TranscriptError::NoSuchDirectory {
+                    line: 3,
+                    path: "/a/b".to_owned(),
+                },
+            ),
+            (
+                "$ ls\n100 a\n$ ls\n100 a\n200 a",
+                TranscriptError::ConflictingListing {
+                    line: 5,
+                    path: "/a".to_owned(),
+                },
+            ),
+            (
+                "$ ls\ndir a\n$ ls\n100 a",
+                TranscriptError::ConflictingListing {
+                    line: 4,
+                    path: "/a".to_owned(),
+                },
+            ),
+            (
+                "$ ls\n100 a\n$ mkdir a",
+                TranscriptError::AlreadyExists {
+                    line: 3,
+                    path: "/a".to_owned(),
+                },
+            ),
+            (
+                "$ mkdir a\n$ cd a\n$ rm x",
+                TranscriptError::NoSuchFile {
+                    line: 3,
+                    path: "/a/x".to_owned(),
+                },
+            ),
+            (
+                "$ mkdir a\n$ cd a\n$ pwd\n/b",
+                TranscriptError::WrongWorkingDirectory {
+                    line: 4,
+                    expected: "/a".to_owned(),
+                    found: "/b".to_owned(),
+                },
+            ),
+        ];
+
+        for (transcript, error) in cases {
+            assert_eq!(replay(transcript).err(), Some(error), "{transcript:?}");
+        }
+    }
+
+    #[test]
+    fn parse_errors_report_their_line() {
+        let cases = [
+            (
+                "$ cd /\n$ mv a b",
+                TranscriptError::UnknownCommand {
+                    line: 2,
+                    text: "$ mv a b".to_owned(),
+                },
+            ),
+            (
+                "$ ls -l",
+                TranscriptError::UnknownCommand {
+                    line: 1,
+                    text: "$ ls -l".to_owned(),
+                },
+            ),
+            (
+                "$ cd /\n100 a",
+                TranscriptError::UnexpectedOutput {
+                    line: 2,
+                    text: "100 a".to_owned(),
+                },
+            ),
+            (
+                "$ pwd\n/\n/",
+                TranscriptError::UnexpectedOutput {
+                    line: 3,
+                    text: "/".to_owned(),
+                },
+            ),
+            (
+                "$ ls\ndir a\nsize b",
+                TranscriptError::InvalidListing {
+                    line: 3,
+                    text: "size b".to_owned(),
+                },
+            ),
+        ];
+
+        for (transcript, error) in cases {
+            assert_eq!(
+                parse_transcript(transcript.lines().map(str::to_owned)).err(),
+                Some(error),
+                "{transcript:?}"
+            );
+        }
     }
 }
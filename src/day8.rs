@@ -1,125 +1,112 @@
-use crate::util::{Coordinates, Direction, Matrix};
+use crate::util::Matrix;
+use crate::visualize::{render_rows, Color, Palette, Visualize};
 use crate::{DaySolution, FromInput};
 
 pub struct Day8(Matrix<isize>);
 
 impl FromInput for Day8 {
     fn from_lines(lines: impl Iterator<Item = String>) -> Self {
-        Self(Matrix {
-            items: lines
-                .map(|line| {
-                    line.chars()
-                        .map(|c| c.to_digit(10).expect("Invalid height") as isize)
-                        .collect()
-                })
-                .collect(),
-        })
+        Self(
+            Matrix::from_lines(lines, |_coords, c| {
+                c.to_digit(10).map(|d| d as isize).ok_or("Invalid height")
+            })
+            .expect("Invalid tree heights"),
+        )
     }
 }
 
 impl DaySolution for Day8 {
     fn part_one(&self) -> String {
-        let mut visible = Matrix::new(self.0.size(), || false);
-
-        for direction in Direction::ALL {
-            self.walk_visible_path(&mut visible, direction);
-        }
-
-        visible
-            .items
+        self.visible_trees()
             .iter()
-            .map(|row| row.iter().filter(|vis| **vis).count())
-            .sum::<usize>()
+            .filter(|vis| **vis)
+            .count()
             .to_string()
     }
 
     fn part_two(&self) -> String {
-        let all_coordinates = (1..(self.0.size().x - 1))
-            .flat_map(|x| (1..(self.0.size().y - 1)).map(move |y| (x, y)))
-            .map(Coordinates::from);
-
-        all_coordinates
-            .map(|coords| self.calculate_scenic_score(coords))
-            .max()
-            .expect("No maximum found")
-            .to_string()
+        let [first, rest @ ..] = self.scan_from_each_side(viewing_distances_to_right);
+        let mut scores = first;
+        for from_side in rest {
+            for (score, distance) in scores.iter_mut().zip(from_side.iter()) {
+                *score *= *distance;
+            }
+        }
+
+        scores.iter().max().expect("No maximum found").to_string()
+    }
+
+    fn visualization(&self) -> Option<&dyn Visualize> {
+        Some(self)
+    }
+}
+
+impl Visualize for Day8 {
+    fn visualize(&self, palette: &Palette) -> String {
+        let visible = self.visible_trees();
+
+        render_rows(self.0.rows().enumerate().map(|(y, row)| {
+            let visible_row = visible.row(y);
+            row.iter()
+                .zip(visible_row)
+                .map(|(height, vis)| match (vis, palette.is_colored()) {
+                    (true, _) => palette.paint(height, Color::Green),
+                    (false, true) => palette.paint(height, Color::Gray),
+                    (false, false) => ".".to_owned(),
+                })
+        }))
     }
 }
 
 impl Day8 {
-    fn walk_visible_path(&self, visible: &mut Matrix<bool>, direction: Direction) {
-        let region_size = visible.size();
-        for start in starting_coordinates_for_direction(region_size, direction) {
-            let mut max_height = -1;
-            let mut coords = start;
-            visible[coords] = true;
-
-            let length = match direction {
-                Direction::Left | Direction::Right => region_size.x,
-                Direction::Up | Direction::Down => region_size.y,
-            };
-
-            for _ in 0..length {
-                let height = self.0[coords];
-                visible[coords] = height > max_height || visible[coords];
-                max_height = std::cmp::max(max_height, height);
-                coords += direction.normal_vector();
+    fn visible_trees(&self) -> Matrix<bool> {
+        let [first, rest @ ..] = self.scan_from_each_side(visible_from_left);
+        let mut visible = first;
+        for from_side in rest {
+            for (vis, vis_from_side) in visible.iter_mut().zip(from_side.iter()) {
+                *vis |= *vis_from_side;
             }
         }
+
+        visible
     }
 
-    fn calculate_scenic_score(&self, coordinates: Coordinates) -> isize {
-        let mut score = 1;
-
-        for direction in Direction::ALL {
-            let max_distance = match direction {
-                Direction::Up => coordinates.y,
-                Direction::Down => self.0.size().y - coordinates.y - 1,
-                Direction::Left => coordinates.x,
-                Direction::Right => self.0.size().x - coordinates.x - 1,
-            };
-
-            let height = self.0[coordinates];
-            let mut travelled = 0;
-            for _ in 0..max_distance {
-                travelled += 1;
-                let coords_to_check = coordinates + direction.normal_vector() * travelled;
-                if self.0[coords_to_check] >= height {
-                    break;
-                }
-            }
+    /// Runs a row scan over the trees as seen from each of the four sides,
+    /// returning each result oriented the same way as the original grid.
+    fn scan_from_each_side<U: Clone>(&self, scan: impl Fn(&[isize]) -> Vec<U>) -> [Matrix<U>; 4] {
+        let scan_rows = |trees: &Matrix<isize>| Matrix::from_rows(trees.rows().map(&scan));
 
-            score *= travelled;
-        }
+        let from_left = scan_rows(&self.0);
+        let from_right = scan_rows(&self.0.flip_horizontal()).flip_horizontal();
+        let from_top = scan_rows(&self.0.transpose()).transpose();
+        let from_bottom = scan_rows(&self.0.rotate_cw()).rotate_ccw();
 
-        score
+        [from_left, from_right, from_top, from_bottom]
     }
 }
 
-fn starting_coordinates_for_direction(
-    region_size: Coordinates,
-    direction: Direction,
-) -> Box<dyn Iterator<Item = Coordinates>> {
-    match direction {
-        Direction::Up => Box::new(
-            (0..region_size.x)
-                .zip(std::iter::repeat(region_size.y - 1))
-                .map(Coordinates::from),
-        ),
-        Direction::Down => Box::new(
-            (0..region_size.x)
-                .zip(std::iter::repeat(0))
-                .map(Coordinates::from),
-        ),
-        Direction::Left => Box::new(
-            std::iter::repeat(region_size.x - 1)
-                .zip(0..region_size.y)
-                .map(Coordinates::from),
-        ),
-        Direction::Right => Box::new(
-            std::iter::repeat(0)
-                .zip(0..region_size.y)
-                .map(Coordinates::from),
-        ),
-    }
+fn visible_from_left(row: &[isize]) -> Vec<bool> {
+    let mut max_height = -1;
+
+    row.iter()
+        .map(|height| {
+            let visible = *height > max_height;
+            max_height = std::cmp::max(max_height, *height);
+            visible
+        })
+        .collect()
+}
+
+fn viewing_distances_to_right(row: &[isize]) -> Vec<usize> {
+    row.iter()
+        .enumerate()
+        .map(|(index, height)| {
+            let trees_to_right = &row[(index + 1)..];
+            trees_to_right
+                .iter()
+                .position(|other| other >= height)
+                .map(|blocker| blocker + 1)
+                .unwrap_or(trees_to_right.len())
+        })
+        .collect()
 }
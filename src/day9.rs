@@ -5,29 +5,21 @@ use nom::character::complete::{anychar, digit1};
 use nom::combinator::map_res;
 use nom::IResult;
 
-use crate::util::Coordinates;
+use crate::animate::Simulation;
+use crate::util::{BoundingBox, Coordinates, Direction};
+use crate::visualize::{render_rows, Color, Palette, Visualize};
 use crate::{DaySolution, FromInput};
 
 pub struct Day9(Vec<Move>);
 
 struct Move {
-    direction: char,
+    direction: Direction,
     distance: usize,
 }
 
 impl Move {
-    fn normal_vector(&self) -> Coordinates {
-        match self.direction {
-            'U' => Coordinates { x: 0, y: 1 },
-            'D' => Coordinates { x: 0, y: -1 },
-            'L' => Coordinates { x: -1, y: 0 },
-            'R' => Coordinates { x: 1, y: 0 },
-            other => panic!("Invalid direction {other}"),
-        }
-    }
-
     fn parse(input: &str) -> IResult<&str, Move> {
-        let (input, direction) = anychar(input)?;
+        let (input, direction) = map_res(anychar, Direction::try_from)(input)?;
         let (input, _) = tag(" ")(input)?;
         let (input, distance) = map_res(digit1, |d: &str| d.parse())(input)?;
 
@@ -55,27 +47,79 @@ impl Rope {
     }
 
     fn move_(&mut self, move_: &Move) {
-        let normal_vector = move_.normal_vector();
-
         for _ in 0..move_.distance {
-            self.segments[0] += normal_vector;
-
-            for index in 0..(self.segments.len() - 1) {
-                let first = &self.segments[index];
-                let second = &self.segments[index + 1];
-
-                let difference = *first - *second;
-                if difference.x.abs() > 1 || difference.y.abs() > 1 {
-                    self.segments[index + 1] += Coordinates {
-                        x: difference.x.signum(),
-                        y: difference.y.signum(),
-                    };
+            self.step(move_.direction);
+        }
+    }
+
+    /// Moves the head of the rope by a single square.
+    fn step(&mut self, direction: Direction) {
+        self.segments[0] += direction.normal_vector();
+
+        for index in 0..(self.segments.len() - 1) {
+            let first = &self.segments[index];
+            let second = &self.segments[index + 1];
+
+            let difference = *first - *second;
+            if difference.chebyshev() > 1 {
+                let direction = difference.direction8().expect("Segments must be apart");
+                self.segments[index + 1] += direction.normal_vector();
+            }
+        }
+
+        let tail = self.segments.last().expect("Rope cannot be empty");
+        self.tail_tracker.insert(*tail);
+    }
+
+    /// Draws the knots of the rope over the squares its tail has visited,
+    /// labelling the head `H` and the other knots by their index.
+    fn render(&self, palette: &Palette) -> String {
+        let bounds = BoundingBox::for_coordinates(self.tail_tracker.iter().chain(&self.segments));
+
+        render_rows((bounds.top_left.y..bounds.bottom_right.y).map(|y| {
+            (bounds.top_left.x..bounds.bottom_right.x).map(move |x| {
+                let coords = Coordinates { x, y };
+                match self.segments.iter().position(|knot| knot == &coords) {
+                    Some(0) => palette.paint('H', Color::Red),
+                    Some(index) => palette.paint(index, Color::Green),
+                    None if coords == Coordinates::default() => palette.paint('s', Color::Red),
+                    None if self.tail_tracker.contains(&coords) => {
+                        palette.paint('#', Color::Yellow)
+                    }
+                    None => palette.paint('.', Color::Gray),
                 }
+            })
+        }))
+    }
+}
+
+/// Plays back the moves one square at a time with a ten-knot rope.
+struct RopeSimulation<'d> {
+    moves: &'d [Move],
+    rope: Rope,
+    move_index: usize,
+    distance_moved: usize,
+}
+
+impl Simulation for RopeSimulation<'_> {
+    fn step(&mut self) -> bool {
+        while let Some(move_) = self.moves.get(self.move_index) {
+            if self.distance_moved < move_.distance {
+                self.rope.step(move_.direction);
+                self.distance_moved += 1;
+
+                return true;
             }
 
-            let tail = self.segments.last().expect("Rope cannot be empty");
-            self.tail_tracker.insert(*tail);
+            self.move_index += 1;
+            self.distance_moved = 0;
         }
+
+        false
+    }
+
+    fn render(&self, palette: &Palette) -> String {
+        self.rope.render(palette)
     }
 }
 
@@ -94,22 +138,58 @@ impl FromInput for Day9 {
 
 impl DaySolution for Day9 {
     fn part_one(&self) -> String {
-        let mut rope = Rope::new(2);
+        self.simulate(2).tail_tracker.len().to_string()
+    }
 
-        for move_ in &self.0 {
-            rope.move_(move_);
-        }
+    fn part_two(&self) -> String {
+        self.simulate(10).tail_tracker.len().to_string()
+    }
 
-        rope.tail_tracker.len().to_string()
+    fn visualization(&self) -> Option<&dyn Visualize> {
+        Some(self)
     }
 
-    fn part_two(&self) -> String {
-        let mut rope = Rope::new(10);
+    fn simulation(&self) -> Option<Box<dyn Simulation + '_>> {
+        Some(Box::new(RopeSimulation {
+            moves: &self.0,
+            rope: Rope::new(10),
+            move_index: 0,
+            distance_moved: 0,
+        }))
+    }
+}
+
+impl Day9 {
+    fn simulate(&self, length: usize) -> Rope {
+        let mut rope = Rope::new(length);
 
         for move_ in &self.0 {
             rope.move_(move_);
         }
 
-        rope.tail_tracker.len().to_string()
+        rope
+    }
+}
+
+/// Draws the cells visited by the tail of the ten-knot rope, with
+/// the starting position marked as `s`.
+impl Visualize for Day9 {
+    fn visualize(&self, palette: &Palette) -> String {
+        let visited = self.simulate(10).tail_tracker;
+        let bounds = BoundingBox::for_coordinates(visited.iter());
+
+        render_rows((bounds.top_left.y..bounds.bottom_right.y).map(|y| {
+            let visited = &visited;
+            (bounds.top_left.x..bounds.bottom_right.x).map(move |x| {
+                let coords = Coordinates { x, y };
+                if coords == Coordinates::default() {
+                    palette.paint('s', Color::Red)
+                } else if visited.contains(&coords) {
+                    palette.paint('#', Color::Yellow)
+                } else {
+                    palette.paint('.', Color::Gray)
+                }
+            })
+        }))
     }
 }
@@ -0,0 +1,465 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::util::{Coordinates, Matrix};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb::new(0, 0, 0);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+    Gif,
+}
+
+impl ImageFormat {
+    /// Picks the format matching the extension of the path.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            "gif" => Some(ImageFormat::Gif),
+            _other => None,
+        }
+    }
+}
+
+/// A picture made of colored pixels, stored row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Image {
+    /// Draws each item of the matrix as a single pixel, colored by the
+    /// item and its coordinates.
+    pub fn from_matrix<T>(matrix: &Matrix<T>, color: impl Fn((Coordinates, &T)) -> Rgb) -> Self {
+        Self {
+            width: matrix.width(),
+            height: matrix.height(),
+            pixels: matrix.enumerate().map(color).collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Blows each pixel up into a `scale` by `scale` square.
+    pub fn scaled(&self, scale: usize) -> Self {
+        let width = self.width * scale;
+        let height = self.height * scale;
+        let pixels = (0..height)
+            .flat_map(|y| {
+                (0..width).map(move |x| self.pixels[(y / scale) * self.width + x / scale])
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Grows the image to the given size, filling new space to the right
+    /// and bottom with the background color.
+    pub fn padded_to(&self, width: usize, height: usize, background: Rgb) -> Self {
+        let pixels = (0..height)
+            .flat_map(|y| {
+                (0..width).map(move |x| {
+                    if x < self.width && y < self.height {
+                        self.pixels[y * self.width + x]
+                    } else {
+                        background
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn write(&self, format: ImageFormat, writer: impl Write) -> io::Result<()> {
+        match format {
+            ImageFormat::Ppm => self.write_ppm(writer),
+            ImageFormat::Png => self.write_png(writer),
+            ImageFormat::Gif => write_gif(std::slice::from_ref(self), Duration::ZERO, writer),
+        }
+    }
+
+    /// Writes the image as a binary (P6) PPM file.
+    pub fn write_ppm(&self, mut writer: impl Write) -> io::Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for pixel in &self.pixels {
+            writer.write_all(&[pixel.r, pixel.g, pixel.b])?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the image as an RGB PNG file, storing the pixel data in
+    /// uncompressed deflate blocks to keep the encoder small.
+    pub fn write_png(&self, mut writer: impl Write) -> io::Result<()> {
+        let mut header = vec![];
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, default compression, filtering, and no interlacing
+        header.extend([8, 2, 0, 0, 0]);
+
+        let mut raw = Vec::with_capacity(self.height * (self.width * 3 + 1));
+        for row in self.pixels.chunks(self.width.max(1)).take(self.height) {
+            raw.push(0);
+            for pixel in row {
+                raw.extend([pixel.r, pixel.g, pixel.b]);
+            }
+        }
+
+        writer.write_all(b"\x89PNG\r\n\x1a\n")?;
+        write_png_chunk(&mut writer, b"IHDR", &header)?;
+        write_png_chunk(&mut writer, b"IDAT", &zlib_stored(&raw))?;
+        write_png_chunk(&mut writer, b"IEND", &[])
+    }
+}
+
+fn write_png_chunk(writer: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+
+    let crc = crc32(kind.iter().chain(data));
+    writer.write_all(&crc.to_be_bytes())
+}
+
+fn crc32<'b>(bytes: impl Iterator<Item = &'b u8>) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+/// Wraps the data in a zlib stream made of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK_SIZE: usize = 0xFFFF;
+
+    let mut stream = vec![0x78, 0x01];
+    let block_count = data.len().div_ceil(MAX_BLOCK_SIZE).max(1);
+    for index in 0..block_count {
+        let block = &data[(index * MAX_BLOCK_SIZE)..data.len().min((index + 1) * MAX_BLOCK_SIZE)];
+        let is_final = index == block_count - 1;
+        let length = block.len() as u16;
+
+        stream.push(is_final as u8);
+        stream.extend(length.to_le_bytes());
+        stream.extend((!length).to_le_bytes());
+        stream.extend(block);
+    }
+
+    stream.extend(adler32(data).to_be_bytes());
+    stream
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + *byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+
+    (b << 16) | a
+}
+
+/// Writes the frames as a looping animated GIF. Smaller frames are padded
+/// to the size of the largest one, and all frames together may use at
+/// most 256 distinct colors.
+pub fn write_gif(
+    frames: &[Image],
+    frame_delay: Duration,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let width = frames.iter().map(Image::width).max().unwrap_or(0);
+    let height = frames.iter().map(Image::height).max().unwrap_or(0);
+    if width > u16::MAX as usize || height > u16::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "GIF images must be smaller than 65536 pixels on each side",
+        ));
+    }
+
+    let mut palette: Vec<Rgb> = vec![];
+    let mut palette_indices: HashMap<Rgb, u8> = HashMap::new();
+    for pixel in frames.iter().flat_map(|frame| &frame.pixels) {
+        if !palette_indices.contains_key(pixel) {
+            if palette.len() == 256 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "GIF images can only use 256 colors",
+                ));
+            }
+
+            palette_indices.insert(*pixel, palette.len() as u8);
+            palette.push(*pixel);
+        }
+    }
+
+    // The color table holds 2^(N + 1) colors, where N is stored in the header
+    let table_bits = (1..=8).find(|bits| palette.len() <= 1 << bits).unwrap_or(8);
+    let min_code_size = table_bits.max(2);
+    let background = palette.first().copied().unwrap_or(Rgb::BLACK);
+
+    writer.write_all(b"GIF89a")?;
+    writer.write_all(&(width as u16).to_le_bytes())?;
+    writer.write_all(&(height as u16).to_le_bytes())?;
+    writer.write_all(&[0xF0 | (table_bits as u8 - 1), 0, 0])?;
+    for index in 0..(1 << table_bits) {
+        let color = palette.get(index).copied().unwrap_or(Rgb::BLACK);
+        writer.write_all(&[color.r, color.g, color.b])?;
+    }
+
+    // Loop the animation forever
+    writer.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+
+    let delay = (frame_delay.as_millis() / 10).min(u16::MAX as u128) as u16;
+    for frame in frames {
+        let frame = frame.padded_to(width, height, background);
+        let indices: Vec<u8> = frame
+            .pixels
+            .iter()
+            .map(|pixel| palette_indices[pixel])
+            .collect();
+
+        writer.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        writer.write_all(&delay.to_le_bytes())?;
+        writer.write_all(&[0x00, 0x00])?;
+
+        writer.write_all(&[0x2C, 0, 0, 0, 0])?;
+        writer.write_all(&(width as u16).to_le_bytes())?;
+        writer.write_all(&(height as u16).to_le_bytes())?;
+        writer.write_all(&[0x00])?;
+
+        writer.write_all(&[min_code_size as u8])?;
+        for block in lzw_encode(&indices, min_code_size).chunks(255) {
+            writer.write_all(&[block.len() as u8])?;
+            writer.write_all(block)?;
+        }
+        writer.write_all(&[0x00])?;
+    }
+
+    writer.write_all(&[0x3B])
+}
+
+/// Compresses color indices with the variable-width LZW flavor used by GIF.
+fn lzw_encode(indices: &[u8], min_code_size: u32) -> Vec<u8> {
+    const MAX_CODE: u16 = 4096;
+
+    let clear_code = 1u16 << min_code_size;
+    let end_code = clear_code + 1;
+
+    let mut output = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end_code + 1;
+
+    output.write(clear_code, code_size);
+
+    let Some((first, rest)) = indices.split_first() else {
+        output.write(end_code, code_size);
+        return output.finish();
+    };
+
+    let mut current = *first as u16;
+    for index in rest {
+        if let Some(code) = table.get(&(current, *index)) {
+            current = *code;
+            continue;
+        }
+
+        output.write(current, code_size);
+        if next_code == MAX_CODE {
+            output.write(clear_code, code_size);
+            table.clear();
+            code_size = min_code_size + 1;
+            next_code = end_code + 1;
+        } else {
+            if next_code >= 1 << code_size {
+                code_size += 1;
+            }
+            table.insert((current, *index), next_code);
+            next_code += 1;
+        }
+
+        current = *index as u16;
+    }
+
+    output.write(current, code_size);
+    output.write(end_code, code_size);
+    output.finish()
+}
+
+/// Packs codes into bytes starting from the least significant bit.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    buffered_bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, bits: u32) {
+        self.buffer |= (code as u32) << self.buffered_bits;
+        self.buffered_bits += bits;
+
+        while self.buffered_bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.buffered_bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.buffered_bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads a GIF LZW stream back into color indices.
+    fn lzw_decode(bytes: &[u8], min_code_size: u32) -> Vec<u8> {
+        let clear_code = 1usize << min_code_size;
+        let end_code = clear_code + 1;
+
+        let mut bits = bytes
+            .iter()
+            .flat_map(|byte| (0..8).map(move |bit| (*byte as usize >> bit) & 1));
+        let mut read =
+            |size: u32| (0..size).fold(0, |code, bit| code | bits.next().unwrap() << bit);
+
+        let mut output = vec![];
+        let mut table: Vec<Vec<u8>> = vec![];
+        let mut code_size = min_code_size + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        loop {
+            let code = read(code_size);
+            if code == clear_code {
+                table = (0..=end_code).map(|index| vec![index as u8]).collect();
+                code_size = min_code_size + 1;
+                previous = None;
+                continue;
+            } else if code == end_code {
+                return output;
+            }
+
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _previous) => entry.clone(),
+                (None, Some(previous)) if code == table.len() => {
+                    [&previous[..], &previous[..1]].concat()
+                }
+                _other => panic!("Unknown code {code}"),
+            };
+            if let Some(previous) = previous {
+                table.push([&previous[..], &entry[..1]].concat());
+                if table.len() == 1 << code_size && code_size < 12 {
+                    code_size += 1;
+                }
+            }
+
+            output.extend(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn crc32_matches_known_values() {
+        assert_eq!(crc32(b"IEND".iter()), 0xAE42_6082);
+        assert_eq!(crc32(b"123456789".iter()), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn adler32_matches_known_values() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn zlib_stored_splits_long_data_into_blocks() {
+        let data: Vec<u8> = (0..70_000).map(|index| (index % 251) as u8).collect();
+        let stream = zlib_stored(&data);
+
+        assert_eq!(stream[..2], [0x78, 0x01]);
+        let mut rest = &stream[2..];
+        let mut inflated: Vec<u8> = vec![];
+        loop {
+            let is_final = rest[0] == 1;
+            let length = u16::from_le_bytes([rest[1], rest[2]]);
+            assert_eq!(u16::from_le_bytes([rest[3], rest[4]]), !length);
+            inflated.extend(&rest[5..5 + length as usize]);
+            rest = &rest[5 + length as usize..];
+            if is_final {
+                break;
+            }
+        }
+
+        assert_eq!(inflated, data);
+        assert_eq!(rest, adler32(&data).to_be_bytes());
+    }
+
+    #[test]
+    fn lzw_round_trips_past_the_code_limit() {
+        // A pseudo-random sequence compresses poorly, so the encoder has
+        // to fill its table and start over several times
+        let mut state = 1u32;
+        let indices: Vec<u8> = (0..100_000)
+            .map(|_index| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8 % 4
+            })
+            .collect();
+
+        let encoded = lzw_encode(&indices, 2);
+        // Codes are at most 12 bits long
+        assert!(encoded.len() * 8 / 12 > 4096);
+        assert_eq!(lzw_decode(&encoded, 2), indices);
+
+        assert_eq!(lzw_decode(&lzw_encode(&[], 2), 2), []);
+        assert_eq!(lzw_decode(&lzw_encode(&[3; 1000], 2), 2), [3; 1000]);
+    }
+}
@@ -1,15 +1,11 @@
-#![feature(
-    slice_group_by,
-    iter_array_chunks,
-    array_chunks,
-    hash_drain_filter,
-    array_windows
-)]
+#![feature(slice_group_by, array_windows)]
 
 use std::env;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, BufWriter};
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
+mod animate;
 mod day1;
 mod day10;
 mod day11;
@@ -27,7 +23,16 @@ mod day6;
 mod day7;
 mod day8;
 mod day9;
+mod image;
+mod trace;
+#[cfg_attr(not(test), allow(dead_code))]
 mod util;
+mod visualize;
+
+use animate::{Playback, Simulation};
+use image::ImageFormat;
+use trace::Trace;
+use visualize::{Palette, Visualize};
 
 /// Reads the lines from the input file into a relevant
 /// model of the data for the day's solution.
@@ -39,6 +44,106 @@ trait FromInput {
 trait DaySolution {
     fn part_one(&self) -> String;
     fn part_two(&self) -> String;
+
+    /// A picture of the day's solution, if the day can draw one.
+    fn visualization(&self) -> Option<&dyn Visualize> {
+        None
+    }
+
+    /// A fresh simulation of the day's puzzle, if the day can be played back.
+    fn simulation(&self) -> Option<Box<dyn Simulation + '_>> {
+        None
+    }
+
+    /// A record of how the day reached its answers, if the day keeps one.
+    fn tracing(&self) -> Option<&dyn Trace> {
+        None
+    }
+}
+
+/// The command line options for the runner.
+struct Options {
+    day: usize,
+    visualize: bool,
+    animate: bool,
+    dump_frames: Option<PathBuf>,
+    image: Option<PathBuf>,
+    scale: usize,
+    trace: Option<PathBuf>,
+    playback: Playback,
+}
+
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        let mut day = None;
+        let mut visualize = false;
+        let mut animate = false;
+        let mut dump_frames = None;
+        let mut image = None;
+        let mut scale = 1;
+        let mut trace = None;
+        let mut playback = Playback::default();
+
+        while let Some(arg) = args.next() {
+            let mut value_for = |flag: &str| {
+                args.next()
+                    .unwrap_or_else(|| panic!("Must provide a value for {flag}"))
+            };
+
+            match arg.as_str() {
+                "--visualize" => visualize = true,
+                "--animate" => animate = true,
+                "--dump-frames" => dump_frames = Some(PathBuf::from(value_for(&arg))),
+                "--image" => image = Some(PathBuf::from(value_for(&arg))),
+                "--trace" => trace = Some(PathBuf::from(value_for(&arg))),
+                "--scale" => {
+                    scale = value_for(&arg)
+                        .parse()
+                        .ok()
+                        .filter(|scale| *scale > 0)
+                        .expect("Scale must be a positive integer")
+                }
+                "--fps" => {
+                    playback.fps = value_for(&arg)
+                        .parse()
+                        .ok()
+                        .filter(|fps: &f32| *fps > 0.0)
+                        .expect("Frame rate must be a positive number")
+                }
+                "--from" => {
+                    playback.from = value_for(&arg)
+                        .parse()
+                        .expect("Starting step must be a non-negative integer")
+                }
+                "--to" => {
+                    playback.to = Some(
+                        value_for(&arg)
+                            .parse()
+                            .expect("Ending step must be a non-negative integer"),
+                    )
+                }
+                flag if flag.starts_with("--") => panic!("Unknown flag {flag}"),
+                day_arg => {
+                    day = Some(
+                        day_arg
+                            .parse::<usize>()
+                            .expect("Provided day wasn't a valid integer"),
+                    )
+                }
+            }
+        }
+
+        Self {
+            day: day.expect("Must provide a day to solve"),
+            visualize,
+            animate,
+            dump_frames,
+            image,
+            scale,
+            trace,
+            playback,
+        }
+    }
 }
 
 /// Reads the input for a day from the `.input` directory.
@@ -88,11 +193,8 @@ fn time_execution(work: impl FnOnce() -> String) -> (String, Duration) {
 }
 
 fn main() {
-    let day = env::args()
-        .nth(1)
-        .expect("Must provide a day to solve")
-        .parse::<usize>()
-        .expect("Provided day wasn't a valid integer");
+    let options = Options::from_args(env::args().skip(1));
+    let day = options.day;
 
     let input = load_input(day);
     let solution = get_day_solution(day, input);
@@ -103,4 +205,69 @@ fn main() {
 
     let (part_two, duration) = time_execution(|| solution.part_two());
     println!("Part 2: {part_two} ({} seconds)", duration.as_secs_f32());
+
+    if options.visualize {
+        match solution.visualization() {
+            Some(visualization) => println!("{}", visualization.visualize(&Palette::for_stdout())),
+            None => println!("Day {day} doesn't have a visualization"),
+        }
+    }
+
+    if let Some(path) = &options.trace {
+        if let Some(tracing) = solution.tracing() {
+            let file = std::fs::File::create(path).expect("Failed to create trace file");
+            let mut writer = BufWriter::new(file);
+            tracing.trace(&mut writer).expect("Failed to write trace");
+            println!("Wrote trace to {}", path.display());
+        } else {
+            println!("Day {day} doesn't have a trace");
+        }
+    }
+
+    if let Some(path) = &options.image {
+        let format =
+            ImageFormat::from_path(path).expect("Image must be a .ppm, .png, or .gif file");
+        if let Some(image) = solution.visualization().and_then(|v| v.image()) {
+            let file = std::fs::File::create(path).expect("Failed to create image file");
+            image
+                .scaled(options.scale)
+                .write(format, BufWriter::new(file))
+                .expect("Failed to write image");
+            println!("Wrote image to {}", path.display());
+        } else {
+            println!("Day {day} can't be drawn as an image");
+        }
+    }
+
+    if options.animate || options.dump_frames.is_some() {
+        let Some(mut simulation) = solution.simulation() else {
+            println!("Day {day} doesn't have a simulation");
+            return;
+        };
+
+        if let Some(path) = &options.dump_frames {
+            let as_gif = ImageFormat::from_path(path) == Some(ImageFormat::Gif);
+            if as_gif && simulation.image().is_none() {
+                println!("Day {day}'s simulation can't be drawn as an image");
+                return;
+            }
+
+            let file =
+                BufWriter::new(std::fs::File::create(path).expect("Failed to create frames file"));
+            if as_gif {
+                options
+                    .playback
+                    .dump_gif(simulation.as_mut(), options.scale, file)
+            } else {
+                options.playback.dump(simulation.as_mut(), file)
+            }
+            .expect("Failed to write frames");
+            println!("Wrote frames to {}", path.display());
+        } else {
+            options
+                .playback
+                .animate(simulation.as_mut(), &Palette::for_stdout())
+                .expect("Failed to draw animation");
+        }
+    }
 }
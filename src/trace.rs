@@ -0,0 +1,8 @@
+use std::io::{self, Write};
+
+/// Records the inner workings of a day's solution, so runs can be
+/// inspected or diffed when an answer is off.
+pub trait Trace {
+    /// Writes the trace as JSON lines, one event per line.
+    fn trace(&self, writer: &mut dyn Write) -> io::Result<()>;
+}
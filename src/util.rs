@@ -1,44 +1,240 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fmt;
-use std::ops::{Index, IndexMut};
+use std::hash::Hash;
+use std::ops::{Add, AddAssign, Index, IndexMut, Mul, Sub, SubAssign};
+use std::str::FromStr;
 
+pub mod ocr;
+
+/// Integer types that can be used for the components of [`Coordinates`].
+///
+/// Only types of at most 64 bits are supported, so that differences
+/// between components always fit in an `i128`.
+pub trait Coordinate:
+    Copy
+    + Ord
+    + Default
+    + fmt::Debug
+    + fmt::Display
+    + Hash
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + AddAssign
+    + SubAssign
+{
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn abs(self) -> Self;
+    fn signum(self) -> Self;
+    fn rem_euclid(self, rhs: Self) -> Self;
+    fn to_index(self) -> Option<usize>;
+    fn from_index(index: usize) -> Option<Self>;
+    fn to_i128(self) -> i128;
+}
+
+macro_rules! impl_coordinate {
+    ($($signed:ty),* ; $($unsigned:ty),*) => {
+        $(
+            impl Coordinate for $signed {
+                const ZERO: Self = 0;
+                const ONE: Self = 1;
+
+                fn abs(self) -> Self {
+                    <$signed>::abs(self)
+                }
+
+                fn signum(self) -> Self {
+                    <$signed>::signum(self)
+                }
+
+                fn rem_euclid(self, rhs: Self) -> Self {
+                    <$signed>::rem_euclid(self, rhs)
+                }
+
+                fn to_index(self) -> Option<usize> {
+                    usize::try_from(self).ok()
+                }
+
+                fn from_index(index: usize) -> Option<Self> {
+                    Self::try_from(index).ok()
+                }
+
+                fn to_i128(self) -> i128 {
+                    i128::try_from(self).expect("Coordinate types fit in an i128")
+                }
+            }
+        )*
+        $(
+            impl Coordinate for $unsigned {
+                const ZERO: Self = 0;
+                const ONE: Self = 1;
+
+                fn abs(self) -> Self {
+                    self
+                }
+
+                fn signum(self) -> Self {
+                    Self::from(self != 0)
+                }
+
+                fn rem_euclid(self, rhs: Self) -> Self {
+                    <$unsigned>::rem_euclid(self, rhs)
+                }
+
+                fn to_index(self) -> Option<usize> {
+                    usize::try_from(self).ok()
+                }
+
+                fn from_index(index: usize) -> Option<Self> {
+                    Self::try_from(index).ok()
+                }
+
+                fn to_i128(self) -> i128 {
+                    i128::try_from(self).expect("Coordinate types fit in an i128")
+                }
+            }
+        )*
+    };
+}
+
+impl_coordinate!(i8, i16, i32, i64, isize; u8, u16, u32, u64, usize);
+
+/// A position or vector on a 2D grid, using screen conventions: `x` grows
+/// to the right and `y` grows downward, so [`Direction::Up`] is `y - 1`.
+///
+/// Coordinates are ordered in reading order, by `y` and then by `x`.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct Coordinates {
-    pub x: isize,
-    pub y: isize,
+pub struct Coordinates<T = isize> {
+    pub x: T,
+    pub y: T,
 }
 
-impl Coordinates {
-    pub fn manhattan(&self) -> isize {
+impl<T: Coordinate> Coordinates<T> {
+    pub fn manhattan(&self) -> T {
         self.x.abs() + self.y.abs()
     }
 
+    /// The number of king moves needed to travel this vector.
+    pub fn chebyshev(&self) -> T {
+        std::cmp::max(self.x.abs(), self.y.abs())
+    }
+
+    pub fn signum(&self) -> Coordinates<T> {
+        Coordinates {
+            x: self.x.signum(),
+            y: self.y.signum(),
+        }
+    }
+
     pub fn direction(&self) -> Option<Direction> {
-        match (self.x, self.y) {
-            (0, y) if y < 0 => Some(Direction::Up),
-            (0, y) if y > 0 => Some(Direction::Down),
-            (x, 0) if x < 0 => Some(Direction::Left),
-            (x, 0) if x > 0 => Some(Direction::Right),
+        match (self.x.cmp(&T::ZERO), self.y.cmp(&T::ZERO)) {
+            (Ordering::Equal, Ordering::Less) => Some(Direction::Up),
+            (Ordering::Equal, Ordering::Greater) => Some(Direction::Down),
+            (Ordering::Less, Ordering::Equal) => Some(Direction::Left),
+            (Ordering::Greater, Ordering::Equal) => Some(Direction::Right),
             _other => None,
         }
     }
 
-    pub fn walk_to(&self, to: Coordinates) -> Option<impl Iterator<Item = Coordinates>> {
-        let direction = (to - *self).direction()?.normal_vector();
+    /// The eight-way direction this vector points in, rounding off any
+    /// slope that isn't horizontal, vertical, or 45 degrees.
+    pub fn direction8(&self) -> Option<Direction8> {
+        match (self.x.cmp(&T::ZERO), self.y.cmp(&T::ZERO)) {
+            (Ordering::Equal, Ordering::Less) => Some(Direction8::Up),
+            (Ordering::Greater, Ordering::Less) => Some(Direction8::UpRight),
+            (Ordering::Greater, Ordering::Equal) => Some(Direction8::Right),
+            (Ordering::Greater, Ordering::Greater) => Some(Direction8::DownRight),
+            (Ordering::Equal, Ordering::Greater) => Some(Direction8::Down),
+            (Ordering::Less, Ordering::Greater) => Some(Direction8::DownLeft),
+            (Ordering::Less, Ordering::Equal) => Some(Direction8::Left),
+            (Ordering::Less, Ordering::Less) => Some(Direction8::UpLeft),
+            (Ordering::Equal, Ordering::Equal) => None,
+        }
+    }
+
+    /// Wraps these coordinates into a torus of the given size.
+    pub fn wrap(&self, size: Coordinates<T>) -> Coordinates<T> {
+        Coordinates {
+            x: self.x.rem_euclid(size.x),
+            y: self.y.rem_euclid(size.y),
+        }
+    }
+
+    /// Converts these coordinates to `(column, row)` indices, failing
+    /// if either is negative or too large for a `usize`.
+    pub fn to_indices(self) -> Option<(usize, usize)> {
+        Some((self.x.to_index()?, self.y.to_index()?))
+    }
+
+    pub fn from_indices(x: usize, y: usize) -> Option<Self> {
+        Some(Self {
+            x: T::from_index(x)?,
+            y: T::from_index(y)?,
+        })
+    }
+
+    /// Rasterizes the line from these coordinates to `to` with Bresenham's
+    /// algorithm, yielding both endpoints. Horizontal, vertical, and 45 degree
+    /// lines are walked exactly, and walking to the same point yields it once.
+    pub fn walk_to(&self, to: Coordinates<T>) -> impl Iterator<Item = Coordinates<T>> {
+        let dx = (to.x.to_i128() - self.x.to_i128()).abs();
+        let dy = -(to.y.to_i128() - self.y.to_i128()).abs();
+        let mut error = dx + dy;
+        let mut next = Some(*self);
+
+        std::iter::from_fn(move || {
+            let current = next?;
+            if current == to {
+                next = None;
+                return Some(current);
+            }
 
-        Some(std::iter::successors(Some(*self), move |coords| {
-            if coords == &to {
-                None
-            } else {
-                Some(*coords + direction)
+            let mut stepped = current;
+            let doubled_error = 2 * error;
+            if doubled_error >= dy {
+                error += dy;
+                stepped.x = step_toward(current.x, to.x);
+            }
+            if doubled_error <= dx {
+                error += dx;
+                stepped.y = step_toward(current.y, to.y);
             }
-        }))
+
+            next = Some(stepped);
+            Some(current)
+        })
+    }
+}
+
+/// Moves one unit from `from` toward `to`, without computing a (possibly
+/// negative) difference of unsigned coordinates.
+fn step_toward<T: Coordinate>(from: T, to: T) -> T {
+    match from.cmp(&to) {
+        Ordering::Less => from + T::ONE,
+        Ordering::Greater => from - T::ONE,
+        Ordering::Equal => from,
     }
 }
 
-impl std::ops::Add for Coordinates {
-    type Output = Coordinates;
+impl<T: Ord> Ord for Coordinates<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.y.cmp(&other.y).then_with(|| self.x.cmp(&other.x))
+    }
+}
+
+impl<T: Ord> PartialOrd for Coordinates<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Coordinate> Add for Coordinates<T> {
+    type Output = Coordinates<T>;
 
-    fn add(self, rhs: Coordinates) -> Self {
+    fn add(self, rhs: Coordinates<T>) -> Self {
         Self {
             x: self.x + rhs.x,
             y: self.y + rhs.y,
@@ -46,17 +242,17 @@ impl std::ops::Add for Coordinates {
     }
 }
 
-impl std::ops::AddAssign for Coordinates {
-    fn add_assign(&mut self, rhs: Coordinates) {
+impl<T: Coordinate> AddAssign for Coordinates<T> {
+    fn add_assign(&mut self, rhs: Coordinates<T>) {
         self.x += rhs.x;
         self.y += rhs.y;
     }
 }
 
-impl std::ops::Sub for Coordinates {
-    type Output = Coordinates;
+impl<T: Coordinate> Sub for Coordinates<T> {
+    type Output = Coordinates<T>;
 
-    fn sub(self, rhs: Coordinates) -> Self {
+    fn sub(self, rhs: Coordinates<T>) -> Self {
         Self {
             x: self.x - rhs.x,
             y: self.y - rhs.y,
@@ -64,10 +260,10 @@ impl std::ops::Sub for Coordinates {
     }
 }
 
-impl std::ops::Mul<isize> for Coordinates {
-    type Output = Coordinates;
+impl<T: Coordinate> Mul<T> for Coordinates<T> {
+    type Output = Coordinates<T>;
 
-    fn mul(self, rhs: isize) -> Self {
+    fn mul(self, rhs: T) -> Self {
         Self {
             x: self.x * rhs,
             y: self.y * rhs,
@@ -75,8 +271,8 @@ impl std::ops::Mul<isize> for Coordinates {
     }
 }
 
-impl From<(isize, isize)> for Coordinates {
-    fn from(coordinates: (isize, isize)) -> Self {
+impl<T> From<(T, T)> for Coordinates<T> {
+    fn from(coordinates: (T, T)) -> Self {
         Self {
             x: coordinates.0,
             y: coordinates.1,
@@ -86,54 +282,318 @@ impl From<(isize, isize)> for Coordinates {
 
 #[derive(Debug, Clone)]
 pub struct Matrix<T> {
-    pub items: Vec<Vec<T>>,
+    items: Vec<T>,
+    width: usize,
+    height: usize,
 }
 
 impl<T> Matrix<T> {
     pub fn new(size: Coordinates, factory: impl Fn() -> T) -> Self {
+        let width = size.x.to_index().unwrap_or(0);
+        let height = size.y.to_index().unwrap_or(0);
+
         Self {
-            items: (0..size.y)
-                .map(|_| (0..size.x).map(|_| factory()).collect())
-                .collect(),
+            items: (0..(width * height)).map(|_| factory()).collect(),
+            width,
+            height,
         }
     }
 
-    pub fn size(&self) -> Coordinates {
-        Coordinates {
-            x: self.items.get(0).map(|row| row.len()).unwrap_or(0) as isize,
-            y: self.items.len() as isize,
+    pub fn from_rows(rows: impl IntoIterator<Item = Vec<T>>) -> Self {
+        let mut items = vec![];
+        let mut width = 0;
+        let mut height = 0;
+
+        for row in rows {
+            if height == 0 {
+                width = row.len();
+            }
+            assert_eq!(row.len(), width, "All matrix rows must be the same length");
+
+            items.extend(row);
+            height += 1;
+        }
+
+        Self {
+            items,
+            width,
+            height,
         }
     }
 
+    /// Parses a grid of characters, one row per line, converting each
+    /// character with `parse_cell`.
+    pub fn from_lines<E>(
+        lines: impl IntoIterator<Item = impl AsRef<str>>,
+        mut parse_cell: impl FnMut(Coordinates, char) -> Result<T, E>,
+    ) -> Result<Self, GridParseError<E>> {
+        let mut items = vec![];
+        let mut width = 0;
+        let mut height = 0;
+
+        for (y, line) in lines.into_iter().enumerate() {
+            let mut row_width = 0;
+            for (x, ch) in line.as_ref().chars().enumerate() {
+                let coords = Coordinates::from_indices(x, y).expect("Grid is too large");
+                let item = parse_cell(coords, ch).map_err(|error| GridParseError::InvalidCell {
+                    coords,
+                    ch,
+                    error,
+                })?;
+
+                items.push(item);
+                row_width += 1;
+            }
+
+            if y == 0 {
+                width = row_width;
+            } else if row_width != width {
+                return Err(GridParseError::Ragged {
+                    row: y,
+                    expected: width,
+                    found: row_width,
+                });
+            }
+
+            height += 1;
+        }
+
+        Ok(Self {
+            items,
+            width,
+            height,
+        })
+    }
+
+    /// Parses a grid like [`Matrix::from_lines`], also collecting the
+    /// positions of every occurrence of the given marker characters.
+    pub fn from_lines_with_markers<E>(
+        lines: impl IntoIterator<Item = impl AsRef<str>>,
+        markers: &[char],
+        mut parse_cell: impl FnMut(Coordinates, char) -> Result<T, E>,
+    ) -> Result<(Self, MarkerPositions), GridParseError<E>> {
+        let mut positions = MarkerPositions::new();
+
+        let matrix = Self::from_lines(lines, |coords, ch| {
+            if markers.contains(&ch) {
+                positions.entry(ch).or_default().push(coords);
+            }
+
+            parse_cell(coords, ch)
+        })?;
+
+        Ok((matrix, positions))
+    }
+
+    pub fn size(&self) -> Coordinates {
+        Coordinates::from_indices(self.width, self.height).expect("Matrix is too large")
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
     pub fn in_bounds(&self, coords: Coordinates) -> bool {
-        coords.x >= 0 && coords.x < self.size().x && coords.y >= 0 && coords.y < self.size().y
+        self.index_of(coords).is_some()
+    }
+
+    fn index_of(&self, coords: Coordinates) -> Option<usize> {
+        let (x, y) = coords.to_indices()?;
+        (x < self.width && y < self.height).then(|| y * self.width + x)
+    }
+
+    fn coordinates_of(&self, index: usize) -> Coordinates {
+        Coordinates::from_indices(index % self.width, index / self.width)
+            .expect("Matrix is too large")
+    }
+
+    pub fn get(&self, coords: Coordinates) -> Option<&T> {
+        self.index_of(coords).map(|index| &self.items[index])
+    }
+
+    pub fn get_mut(&mut self, coords: Coordinates) -> Option<&mut T> {
+        self.index_of(coords).map(|index| &mut self.items[index])
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.items[(y * self.width)..((y + 1) * self.width)]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [T] {
+        &mut self.items[(y * self.width)..((y + 1) * self.width)]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        (0..self.height).map(|y| self.row(y))
+    }
+
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        assert!(x < self.width, "Column index out of bounds");
+
+        self.items.iter().skip(x).step_by(self.width)
+    }
+
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = &T>> {
+        (0..self.width).map(|x| self.column(x))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.items.iter_mut()
+    }
+
+    /// Iterates over every item in reading order alongside its coordinates.
+    pub fn enumerate(&self) -> impl Iterator<Item = (Coordinates, &T)> {
+        self.items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| (self.coordinates_of(index), item))
     }
 
-    pub fn neighbors(&self, coords: Coordinates) -> Vec<Coordinates> {
-        Direction::ALL
+    pub fn view(&self, bounds: BoundingBox) -> MatrixView<'_, T> {
+        assert!(
+            bounds.top_left.x >= 0
+                && bounds.top_left.y >= 0
+                && bounds.top_left.x <= bounds.bottom_right.x
+                && bounds.top_left.y <= bounds.bottom_right.y
+                && bounds.bottom_right.x <= self.size().x
+                && bounds.bottom_right.y <= self.size().y,
+            "View bounds must lie within the matrix"
+        );
+
+        MatrixView {
+            matrix: self,
+            bounds,
+        }
+    }
+
+    pub fn neighbors(&self, coords: Coordinates) -> impl Iterator<Item = Coordinates> + '_ {
+        self.neighbors_by(coords, Direction::ALL.map(|d| d.normal_vector()))
+    }
+
+    pub fn neighbors8(&self, coords: Coordinates) -> impl Iterator<Item = Coordinates> + '_ {
+        self.neighbors_by(coords, Direction8::ALL.map(|d| d.normal_vector()))
+    }
+
+    /// The in-bounds coordinates reached by each of the given steps.
+    pub fn neighbors_by(
+        &self,
+        coords: Coordinates,
+        steps: impl IntoIterator<Item = Coordinates> + 'static,
+    ) -> impl Iterator<Item = Coordinates> + '_ {
+        steps
             .into_iter()
-            .filter_map(|direction| {
-                Some(coords + direction.normal_vector())
-                    .filter(|new_coords| self.in_bounds(*new_coords))
+            .map(move |step| coords + step)
+            .filter(|neighbor| self.in_bounds(*neighbor))
+    }
+
+    /// The coordinates reached by each of the given steps, wrapping
+    /// around the edges of the matrix as if it were a torus.
+    pub fn wrapping_neighbors(
+        &self,
+        coords: Coordinates,
+        steps: impl IntoIterator<Item = Coordinates> + 'static,
+    ) -> impl Iterator<Item = Coordinates> + '_ {
+        steps
+            .into_iter()
+            .map(move |step| (coords + step).wrap(self.size()))
+    }
+}
+
+impl<T: Clone> Matrix<T> {
+    /// Builds a new matrix of the given size, pulling each item from
+    /// the coordinates in this matrix that `source` maps it to.
+    fn remap(&self, size: Coordinates, source: impl Fn(Coordinates) -> Coordinates) -> Self {
+        let (width, height) = size.to_indices().expect("Matrix size must not be negative");
+        let items = (0..(width * height))
+            .map(|index| {
+                let coords = Coordinates::from_indices(index % width, index / width)
+                    .expect("Matrix is too large");
+                self[source(coords)].clone()
             })
-            .collect()
+            .collect();
+
+        Self {
+            items,
+            width,
+            height,
+        }
+    }
+
+    pub fn transpose(&self) -> Self {
+        let size = self.size();
+        self.remap(
+            Coordinates {
+                x: size.y,
+                y: size.x,
+            },
+            |c| Coordinates { x: c.y, y: c.x },
+        )
+    }
+
+    pub fn rotate_cw(&self) -> Self {
+        let size = self.size();
+        self.remap(
+            Coordinates {
+                x: size.y,
+                y: size.x,
+            },
+            |c| Coordinates {
+                x: c.y,
+                y: size.y - 1 - c.x,
+            },
+        )
+    }
+
+    pub fn rotate_ccw(&self) -> Self {
+        let size = self.size();
+        self.remap(
+            Coordinates {
+                x: size.y,
+                y: size.x,
+            },
+            |c| Coordinates {
+                x: size.x - 1 - c.y,
+                y: c.x,
+            },
+        )
+    }
+
+    /// Mirrors the matrix left-to-right.
+    pub fn flip_horizontal(&self) -> Self {
+        let size = self.size();
+        self.remap(size, |c| Coordinates {
+            x: size.x - 1 - c.x,
+            y: c.y,
+        })
+    }
+
+    /// Mirrors the matrix top-to-bottom.
+    pub fn flip_vertical(&self) -> Self {
+        let size = self.size();
+        self.remap(size, |c| Coordinates {
+            x: c.x,
+            y: size.y - 1 - c.y,
+        })
     }
 }
 
 impl<T: fmt::Display> fmt::Display for Matrix<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for (index, row) in self.items.iter().enumerate() {
-            write!(
-                f,
-                "{}",
-                row.iter()
-                    .map(|item| item.to_string())
-                    .collect::<Vec<String>>()
-                    .join("")
-            )?;
-
-            if index < self.items.len() - 1 {
-                write!(f, "\n")?;
+        for (index, row) in self.rows().enumerate() {
+            for item in row {
+                write!(f, "{item}")?;
+            }
+
+            if index < self.height - 1 {
+                writeln!(f)?;
             }
         }
 
@@ -145,17 +605,120 @@ impl<T> Index<Coordinates> for Matrix<T> {
     type Output = T;
 
     fn index(&self, coordinates: Coordinates) -> &Self::Output {
-        &self.items[coordinates.y as usize][coordinates.x as usize]
+        self.get(coordinates).expect("Coordinates out of bounds")
     }
 }
 
 impl<T> IndexMut<Coordinates> for Matrix<T> {
     fn index_mut(&mut self, coordinates: Coordinates) -> &mut Self::Output {
-        &mut self.items[coordinates.y as usize][coordinates.x as usize]
+        self.get_mut(coordinates)
+            .expect("Coordinates out of bounds")
+    }
+}
+
+/// A borrowed rectangular region of a [`Matrix`], indexed relative
+/// to the top left corner of the region.
+#[derive(Debug, Clone, Copy)]
+pub struct MatrixView<'m, T> {
+    matrix: &'m Matrix<T>,
+    bounds: BoundingBox,
+}
+
+impl<'m, T> MatrixView<'m, T> {
+    pub fn size(&self) -> Coordinates {
+        self.bounds.bottom_right - self.bounds.top_left
+    }
+
+    pub fn in_bounds(&self, coords: Coordinates) -> bool {
+        let size = self.size();
+        coords.x >= 0 && coords.x < size.x && coords.y >= 0 && coords.y < size.y
+    }
+
+    pub fn get(&self, coords: Coordinates) -> Option<&'m T> {
+        if self.in_bounds(coords) {
+            self.matrix.get(coords + self.bounds.top_left)
+        } else {
+            None
+        }
+    }
+
+    pub fn row(&self, y: usize) -> &'m [T] {
+        let (left, top) = self
+            .bounds
+            .top_left
+            .to_indices()
+            .expect("View is in bounds");
+        let (right, bottom) = self
+            .bounds
+            .bottom_right
+            .to_indices()
+            .expect("View is in bounds");
+        assert!(top + y < bottom, "Row index out of bounds");
+
+        &self.matrix.row(top + y)[left..right]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &'m [T]> + '_ {
+        let (_width, height) = self.size().to_indices().expect("View is in bounds");
+        (0..height).map(|y| self.row(y))
+    }
+
+    pub fn to_matrix(&self) -> Matrix<T>
+    where
+        T: Clone,
+    {
+        Matrix::from_rows(self.rows().map(|row| row.to_vec()))
+    }
+}
+
+impl<T> Index<Coordinates> for MatrixView<'_, T> {
+    type Output = T;
+
+    fn index(&self, coordinates: Coordinates) -> &Self::Output {
+        self.get(coordinates).expect("Coordinates out of bounds")
+    }
+}
+
+/// The coordinates of each marker character found while parsing a grid.
+pub type MarkerPositions = HashMap<char, Vec<Coordinates>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GridParseError<E> {
+    Ragged {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    InvalidCell {
+        coords: Coordinates,
+        ch: char,
+        error: E,
+    },
+}
+
+impl<E: fmt::Display> fmt::Display for GridParseError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GridParseError::Ragged {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Row {row} has {found} cells, but previous rows have {expected}"
+            ),
+            GridParseError::InvalidCell { coords, ch, error } => write!(
+                f,
+                "Invalid character {ch:?} at ({}, {}): {error}",
+                coords.x, coords.y
+            ),
+        }
     }
 }
 
-#[derive(Debug)]
+impl<E: fmt::Debug + fmt::Display> std::error::Error for GridParseError<E> {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BoundingBox {
     pub top_left: Coordinates,
     pub bottom_right: Coordinates,
@@ -185,9 +748,140 @@ impl BoundingBox {
             bottom_right: bottom_right + Coordinates { x: 1, y: 1 },
         }
     }
+
+    pub fn size(&self) -> Coordinates {
+        self.bottom_right - self.top_left
+    }
+
+    pub fn contains(&self, coords: Coordinates) -> bool {
+        (self.top_left.x..self.bottom_right.x).contains(&coords.x)
+            && (self.top_left.y..self.bottom_right.y).contains(&coords.y)
+    }
+
+    /// The smallest bounding box covering both this box and the coordinates.
+    pub fn including(&self, coords: Coordinates) -> Self {
+        Self {
+            top_left: Coordinates {
+                x: std::cmp::min(self.top_left.x, coords.x),
+                y: std::cmp::min(self.top_left.y, coords.y),
+            },
+            bottom_right: Coordinates {
+                x: std::cmp::max(self.bottom_right.x, coords.x + 1),
+                y: std::cmp::max(self.bottom_right.y, coords.y + 1),
+            },
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// An unbounded grid that only stores the cells that have been set,
+/// reading every other cell as a default value.
+#[derive(Debug, Clone)]
+pub struct SparseGrid<T> {
+    cells: HashMap<Coordinates, T>,
+    default: T,
+    bounds: Option<BoundingBox>,
+}
+
+impl<T> SparseGrid<T> {
+    pub fn new(default: T) -> Self {
+        Self {
+            cells: HashMap::new(),
+            default,
+            bounds: None,
+        }
+    }
+
+    pub fn get(&self, coords: Coordinates) -> &T {
+        self.cells.get(&coords).unwrap_or(&self.default)
+    }
+
+    pub fn insert(&mut self, coords: Coordinates, value: T) -> Option<T> {
+        self.bounds = Some(match self.bounds {
+            Some(bounds) => bounds.including(coords),
+            None => BoundingBox {
+                top_left: coords,
+                bottom_right: coords + Coordinates { x: 1, y: 1 },
+            },
+        });
+
+        self.cells.insert(coords, value)
+    }
+
+    pub fn remove(&mut self, coords: Coordinates) -> Option<T> {
+        self.cells.remove(&coords)
+    }
+
+    pub fn contains(&self, coords: Coordinates) -> bool {
+        self.cells.contains_key(&coords)
+    }
+
+    /// The number of cells that have been explicitly set.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// The smallest box covering every cell that has ever been set. This
+    /// doesn't shrink when cells are removed.
+    pub fn bounds(&self) -> Option<BoundingBox> {
+        self.bounds
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Coordinates, &T)> {
+        self.cells.iter().map(|(coords, value)| (*coords, value))
+    }
+
+    /// Copies the cells within the current bounds into a dense matrix,
+    /// whose origin is the top left corner of the bounds.
+    pub fn to_matrix(&self) -> Matrix<T>
+    where
+        T: Clone,
+    {
+        let Some(bounds) = self.bounds else {
+            return Matrix::new(Coordinates::default(), || self.default.clone());
+        };
+
+        let mut matrix = Matrix::new(bounds.size(), || self.default.clone());
+        for (coords, value) in self.iter() {
+            matrix[coords - bounds.top_left] = value.clone();
+        }
+
+        matrix
+    }
+}
+
+impl<T> Index<Coordinates> for SparseGrid<T> {
+    type Output = T;
+
+    fn index(&self, coordinates: Coordinates) -> &Self::Output {
+        self.get(coordinates)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for SparseGrid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some(bounds) = self.bounds else {
+            return Ok(());
+        };
+
+        for y in bounds.top_left.y..bounds.bottom_right.y {
+            for x in bounds.top_left.x..bounds.bottom_right.x {
+                write!(f, "{}", self.get(Coordinates { x, y }))?;
+            }
+
+            if y < bounds.bottom_right.y - 1 {
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Direction {
     Up,
     Down,
@@ -211,4 +905,790 @@ impl Direction {
             Direction::Right => Coordinates { x: 1, y: 0 },
         }
     }
+
+    /// The direction after a 90 degree counterclockwise turn.
+    pub fn turn_left(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+        }
+    }
+
+    /// The direction after a 90 degree clockwise turn.
+    pub fn turn_right(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+/// Parses the letters puzzles use for directions: `U`/`D`/`L`/`R`,
+/// compass points `N`/`S`/`W`/`E`, and arrows `^`/`v`/`<`/`>`.
+impl TryFrom<char> for Direction {
+    type Error = ParseDirectionError;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        match c {
+            'U' | 'N' | '^' => Ok(Direction::Up),
+            'D' | 'S' | 'v' => Ok(Direction::Down),
+            'L' | 'W' | '<' => Ok(Direction::Left),
+            'R' | 'E' | '>' => Ok(Direction::Right),
+            other => Err(ParseDirectionError(other.to_string())),
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = ParseDirectionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Direction::try_from(c),
+            _other => Err(ParseDirectionError(s.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDirectionError(pub String);
+
+impl fmt::Display for ParseDirectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid direction {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseDirectionError {}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Coordinates3 {
+    pub x: isize,
+    pub y: isize,
+    pub z: isize,
+}
+
+impl Coordinates3 {
+    pub const FACE_DIRECTIONS: [Coordinates3; 6] = [
+        Coordinates3 { x: 0, y: 0, z: 1 },
+        Coordinates3 { x: 0, y: 0, z: -1 },
+        Coordinates3 { x: 0, y: 1, z: 0 },
+        Coordinates3 { x: 0, y: -1, z: 0 },
+        Coordinates3 { x: 1, y: 0, z: 0 },
+        Coordinates3 { x: -1, y: 0, z: 0 },
+    ];
+
+    pub fn manhattan(&self) -> isize {
+        self.x.abs() + self.y.abs() + self.z.abs()
+    }
+
+    /// The 6 coordinates that share a face with these coordinates.
+    pub fn face_neighbors(&self) -> [Coordinates3; 6] {
+        Self::FACE_DIRECTIONS.map(|direction| *self + direction)
+    }
+
+    /// The 26 coordinates that share a face, edge, or corner with these coordinates.
+    pub fn all_neighbors(&self) -> [Coordinates3; 26] {
+        let mut neighbors = [*self; 26];
+        let deltas = (-1..=1)
+            .flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| (x, y, z))))
+            .map(Coordinates3::from)
+            .filter(|delta| delta != &Coordinates3::default());
+
+        for (neighbor, delta) in neighbors.iter_mut().zip(deltas) {
+            *neighbor += delta;
+        }
+
+        neighbors
+    }
+}
+
+impl std::ops::Add for Coordinates3 {
+    type Output = Coordinates3;
+
+    fn add(self, rhs: Coordinates3) -> Self {
+        Self {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+
+impl std::ops::AddAssign for Coordinates3 {
+    fn add_assign(&mut self, rhs: Coordinates3) {
+        self.x += rhs.x;
+        self.y += rhs.y;
+        self.z += rhs.z;
+    }
+}
+
+impl std::ops::Sub for Coordinates3 {
+    type Output = Coordinates3;
+
+    fn sub(self, rhs: Coordinates3) -> Self {
+        Self {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+            z: self.z - rhs.z,
+        }
+    }
+}
+
+impl std::ops::SubAssign for Coordinates3 {
+    fn sub_assign(&mut self, rhs: Coordinates3) {
+        self.x -= rhs.x;
+        self.y -= rhs.y;
+        self.z -= rhs.z;
+    }
+}
+
+impl std::ops::Mul<isize> for Coordinates3 {
+    type Output = Coordinates3;
+
+    fn mul(self, rhs: isize) -> Self {
+        Self {
+            x: self.x * rhs,
+            y: self.y * rhs,
+            z: self.z * rhs,
+        }
+    }
+}
+
+impl std::ops::Neg for Coordinates3 {
+    type Output = Coordinates3;
+
+    fn neg(self) -> Self {
+        Self {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+}
+
+impl From<(isize, isize, isize)> for Coordinates3 {
+    fn from(coordinates: (isize, isize, isize)) -> Self {
+        Self {
+            x: coordinates.0,
+            y: coordinates.1,
+            z: coordinates.2,
+        }
+    }
+}
+
+/// A box of 3D coordinates, with an inclusive `start` and an exclusive
+/// `end` like the `bottom_right` of a [`BoundingBox`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BoundingBox3 {
+    pub start: Coordinates3,
+    pub end: Coordinates3,
+}
+
+impl BoundingBox3 {
+    pub fn for_coordinates<'c>(all_coords: impl Iterator<Item = &'c Coordinates3>) -> Self {
+        let mut start = Coordinates3 {
+            x: isize::MAX,
+            y: isize::MAX,
+            z: isize::MAX,
+        };
+        let mut end = Coordinates3 {
+            x: isize::MIN,
+            y: isize::MIN,
+            z: isize::MIN,
+        };
+
+        for coords in all_coords {
+            start.x = std::cmp::min(coords.x, start.x);
+            start.y = std::cmp::min(coords.y, start.y);
+            start.z = std::cmp::min(coords.z, start.z);
+
+            end.x = std::cmp::max(coords.x + 1, end.x);
+            end.y = std::cmp::max(coords.y + 1, end.y);
+            end.z = std::cmp::max(coords.z + 1, end.z);
+        }
+
+        // No coordinates make an empty box rather than an inverted one
+        if start.x > end.x {
+            return Self::default();
+        }
+
+        Self { start, end }
+    }
+
+    pub fn padded(&self, amount: isize) -> Self {
+        let padding = Coordinates3 {
+            x: amount,
+            y: amount,
+            z: amount,
+        };
+
+        Self {
+            start: self.start - padding,
+            end: self.end + padding,
+        }
+    }
+
+    pub fn size(&self) -> Coordinates3 {
+        let size = self.end - self.start;
+
+        Coordinates3 {
+            x: size.x.max(0),
+            y: size.y.max(0),
+            z: size.z.max(0),
+        }
+    }
+
+    pub fn volume(&self) -> Option<usize> {
+        let size = self.size();
+
+        (size.x as usize)
+            .checked_mul(size.y as usize)?
+            .checked_mul(size.z as usize)
+    }
+
+    pub fn contains(&self, coords: Coordinates3) -> bool {
+        (self.start.x..self.end.x).contains(&coords.x)
+            && (self.start.y..self.end.y).contains(&coords.y)
+            && (self.start.z..self.end.z).contains(&coords.z)
+    }
+
+    /// The position of the coordinates in a flat, x-major array covering this box.
+    pub fn index_of(&self, coords: Coordinates3) -> Option<usize> {
+        if !self.contains(coords) {
+            return None;
+        }
+
+        let size = self.size();
+        let offset = coords - self.start;
+
+        Some(((offset.z * size.y + offset.y) * size.x + offset.x) as usize)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Grid3<T> {
+    bounds: BoundingBox3,
+    items: Vec<T>,
+}
+
+impl<T> Grid3<T> {
+    pub fn new(bounds: BoundingBox3, factory: impl Fn() -> T) -> Self {
+        let volume = bounds.volume().expect("Grid bounds are too large");
+
+        Self {
+            bounds,
+            items: (0..volume).map(|_| factory()).collect(),
+        }
+    }
+
+    pub fn bounds(&self) -> BoundingBox3 {
+        self.bounds
+    }
+
+    pub fn in_bounds(&self, coords: Coordinates3) -> bool {
+        self.bounds.contains(coords)
+    }
+
+    pub fn get(&self, coords: Coordinates3) -> Option<&T> {
+        self.bounds.index_of(coords).map(|index| &self.items[index])
+    }
+
+    pub fn get_mut(&mut self, coords: Coordinates3) -> Option<&mut T> {
+        self.bounds
+            .index_of(coords)
+            .map(|index| &mut self.items[index])
+    }
+
+    pub fn neighbors(&self, coords: Coordinates3) -> impl Iterator<Item = Coordinates3> + '_ {
+        coords
+            .face_neighbors()
+            .into_iter()
+            .filter(|neighbor| self.in_bounds(*neighbor))
+    }
+}
+
+impl<T> Index<Coordinates3> for Grid3<T> {
+    type Output = T;
+
+    fn index(&self, coordinates: Coordinates3) -> &Self::Output {
+        self.get(coordinates).expect("Coordinates out of bounds")
+    }
+}
+
+impl<T> IndexMut<Coordinates3> for Grid3<T> {
+    fn index_mut(&mut self, coordinates: Coordinates3) -> &mut Self::Output {
+        self.get_mut(coordinates)
+            .expect("Coordinates out of bounds")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction8 {
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+}
+
+impl Direction8 {
+    pub const ALL: [Direction8; 8] = [
+        Direction8::Up,
+        Direction8::UpRight,
+        Direction8::Right,
+        Direction8::DownRight,
+        Direction8::Down,
+        Direction8::DownLeft,
+        Direction8::Left,
+        Direction8::UpLeft,
+    ];
+
+    pub fn normal_vector(&self) -> Coordinates {
+        match self {
+            Direction8::Up => Coordinates { x: 0, y: -1 },
+            Direction8::UpRight => Coordinates { x: 1, y: -1 },
+            Direction8::Right => Coordinates { x: 1, y: 0 },
+            Direction8::DownRight => Coordinates { x: 1, y: 1 },
+            Direction8::Down => Coordinates { x: 0, y: 1 },
+            Direction8::DownLeft => Coordinates { x: -1, y: 1 },
+            Direction8::Left => Coordinates { x: -1, y: 0 },
+            Direction8::UpLeft => Coordinates { x: -1, y: -1 },
+        }
+    }
+}
+
+impl From<Direction> for Direction8 {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::Up => Direction8::Up,
+            Direction::Down => Direction8::Down,
+            Direction::Left => Direction8::Left,
+            Direction::Right => Direction8::Right,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walk(from: (isize, isize), to: (isize, isize)) -> Vec<(isize, isize)> {
+        Coordinates::from(from)
+            .walk_to(Coordinates::from(to))
+            .map(|coords| (coords.x, coords.y))
+            .collect()
+    }
+
+    #[test]
+    fn walk_to_same_point_yields_it_once() {
+        assert_eq!(walk((3, 4), (3, 4)), [(3, 4)]);
+    }
+
+    #[test]
+    fn walk_to_includes_both_endpoints() {
+        assert_eq!(walk((2, 0), (5, 0)), [(2, 0), (3, 0), (4, 0), (5, 0)]);
+        assert_eq!(walk((0, 2), (0, -1)), [(0, 2), (0, 1), (0, 0), (0, -1)]);
+    }
+
+    #[test]
+    fn walk_to_follows_45_degree_lines() {
+        assert_eq!(walk((0, 0), (3, 3)), [(0, 0), (1, 1), (2, 2), (3, 3)]);
+        assert_eq!(walk((3, 0), (0, 3)), [(3, 0), (2, 1), (1, 2), (0, 3)]);
+        assert_eq!(walk((1, 1), (-1, -1)), [(1, 1), (0, 0), (-1, -1)]);
+    }
+
+    #[test]
+    fn walk_to_rasterizes_other_slopes() {
+        assert_eq!(
+            walk((0, 0), (5, 2)),
+            [(0, 0), (1, 0), (2, 1), (3, 1), (4, 2), (5, 2)]
+        );
+        assert_eq!(walk((0, 0), (1, 3)), [(0, 0), (0, 1), (1, 2), (1, 3)]);
+        assert_eq!(walk((1, 3), (0, 0)), [(1, 3), (1, 2), (0, 1), (0, 0)]);
+    }
+
+    #[test]
+    fn walk_to_handles_extreme_coordinates() {
+        let from = Coordinates {
+            x: i64::MIN,
+            y: i64::MAX,
+        };
+        let to = Coordinates {
+            x: i64::MAX,
+            y: i64::MIN,
+        };
+        assert_eq!(
+            from.walk_to(to).nth(2),
+            Some(Coordinates {
+                x: i64::MIN + 2,
+                y: i64::MAX - 2
+            })
+        );
+
+        let from = Coordinates { x: 0, y: 0 };
+        let to = Coordinates {
+            x: u64::MAX,
+            y: u64::MAX / 2,
+        };
+        assert_eq!(
+            from.walk_to(to).take(3).collect::<Vec<_>>(),
+            [(0, 0), (1, 0), (2, 1)].map(Coordinates::from)
+        );
+        assert_eq!(
+            to.walk_to(Coordinates {
+                x: u64::MAX - 2,
+                y: u64::MAX / 2
+            })
+            .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn walk_to_works_with_unsigned_coordinates() {
+        let walked: Vec<_> = Coordinates { x: 2usize, y: 0 }
+            .walk_to(Coordinates { x: 0, y: 2 })
+            .collect();
+
+        assert_eq!(walked, [(2, 0), (1, 1), (0, 2)].map(Coordinates::from));
+    }
+
+    fn numbered_matrix() -> Matrix<usize> {
+        Matrix::from_rows((0..4).map(|y| (0..5).map(|x| y * 10 + x).collect()))
+    }
+
+    fn view_bounds(left: isize, top: isize, right: isize, bottom: isize) -> BoundingBox {
+        BoundingBox {
+            top_left: Coordinates { x: left, y: top },
+            bottom_right: Coordinates {
+                x: right,
+                y: bottom,
+            },
+        }
+    }
+
+    #[test]
+    fn view_is_indexed_from_its_corner() {
+        let matrix = numbered_matrix();
+        let view = matrix.view(view_bounds(1, 2, 4, 4));
+
+        assert_eq!(view.size(), Coordinates { x: 3, y: 2 });
+        assert_eq!(view[Coordinates { x: 0, y: 0 }], 21);
+        assert_eq!(view[Coordinates { x: 2, y: 1 }], 33);
+        assert_eq!(view.get(Coordinates { x: 3, y: 0 }), None);
+        assert_eq!(view.get(Coordinates { x: 0, y: -1 }), None);
+    }
+
+    #[test]
+    fn view_rows_and_copies() {
+        let matrix = numbered_matrix();
+        let view = matrix.view(view_bounds(1, 2, 4, 4));
+
+        assert_eq!(view.row(1), [31, 32, 33]);
+        assert_eq!(
+            view.rows().collect::<Vec<_>>(),
+            [[21, 22, 23], [31, 32, 33]]
+        );
+
+        let copy = view.to_matrix();
+        assert_eq!(copy.size(), Coordinates { x: 3, y: 2 });
+        assert_eq!(
+            copy.rows().collect::<Vec<_>>(),
+            [[21, 22, 23], [31, 32, 33]]
+        );
+    }
+
+    #[test]
+    fn view_can_cover_the_whole_matrix() {
+        let matrix = numbered_matrix();
+        let view = matrix.view(view_bounds(0, 0, 5, 4));
+
+        assert!(view.rows().eq(matrix.rows()));
+    }
+
+    #[test]
+    #[should_panic(expected = "View bounds must lie within the matrix")]
+    fn view_outside_the_matrix_panics() {
+        numbered_matrix().view(view_bounds(2, 0, 6, 4));
+    }
+
+    #[test]
+    #[should_panic(expected = "View bounds must lie within the matrix")]
+    fn inverted_view_panics() {
+        numbered_matrix().view(view_bounds(3, 0, 1, 4));
+    }
+
+    #[test]
+    #[should_panic(expected = "Row index out of bounds")]
+    fn view_row_outside_the_view_panics() {
+        numbered_matrix().view(view_bounds(0, 1, 5, 3)).row(2);
+    }
+
+    #[test]
+    fn coordinates3_neighborhoods() {
+        let center = Coordinates3 { x: 1, y: -2, z: 3 };
+        assert_eq!(center.manhattan(), 6);
+
+        let neighbors = center.all_neighbors();
+        let unique: std::collections::HashSet<_> = neighbors.iter().collect();
+        assert_eq!(unique.len(), 26);
+        assert!(!unique.contains(&center));
+        assert!(neighbors
+            .iter()
+            .all(|neighbor| (*neighbor - center).manhattan() <= 3));
+        assert!(center
+            .face_neighbors()
+            .iter()
+            .all(|neighbor| unique.contains(neighbor)));
+    }
+
+    #[test]
+    fn grid3_bounds_and_neighbors() {
+        let bounds =
+            BoundingBox3::for_coordinates([(0, 0, 0), (2, 1, 1)].map(Coordinates3::from).iter());
+        let mut grid = Grid3::new(bounds, || 0);
+
+        assert_eq!(grid.bounds(), bounds);
+        assert_eq!(bounds.volume(), Some(12));
+        assert!(grid.in_bounds(Coordinates3 { x: 2, y: 1, z: 1 }));
+        assert!(!grid.in_bounds(Coordinates3 { x: 3, y: 0, z: 0 }));
+        assert!(!grid.in_bounds(Coordinates3 { x: 0, y: -1, z: 0 }));
+
+        grid[Coordinates3 { x: 1, y: 0, z: 1 }] = 5;
+        assert_eq!(grid.get(Coordinates3 { x: 1, y: 0, z: 1 }), Some(&5));
+        assert_eq!(grid.get(Coordinates3 { x: 1, y: 0, z: 2 }), None);
+
+        assert_eq!(grid.neighbors(Coordinates3::default()).count(), 3);
+        assert_eq!(grid.neighbors(Coordinates3 { x: 1, y: 0, z: 0 }).count(), 4);
+    }
+
+    #[test]
+    fn empty_bounding_box3() {
+        let bounds = BoundingBox3::for_coordinates(std::iter::empty());
+
+        assert_eq!(bounds.volume(), Some(0));
+        assert!(!bounds.contains(Coordinates3::default()));
+        assert_eq!(Grid3::new(bounds, || 0).get(Coordinates3::default()), None);
+    }
+
+    #[test]
+    fn matrix_rows_and_columns() {
+        let mut matrix = numbered_matrix();
+
+        assert_eq!(
+            matrix.column(3).copied().collect::<Vec<_>>(),
+            [3, 13, 23, 33]
+        );
+        assert_eq!(matrix.columns().count(), 5);
+        assert!(matrix
+            .columns()
+            .zip(matrix.transpose().rows())
+            .all(|(column, row)| column.eq(row)));
+
+        matrix.row_mut(1).fill(0);
+        assert_eq!(matrix.row(1), [0; 5]);
+        assert_eq!(matrix.row(2), [20, 21, 22, 23, 24]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Column index out of bounds")]
+    fn matrix_column_outside_the_matrix_panics() {
+        numbered_matrix().column(5).next();
+    }
+
+    #[test]
+    fn matrix_flips() {
+        let matrix = numbered_matrix();
+        let flipped = matrix.flip_vertical();
+
+        assert_eq!(flipped.row(0), matrix.row(3));
+        assert_eq!(flipped.row(3), matrix.row(0));
+        assert!(flipped.flip_vertical().rows().eq(matrix.rows()));
+        assert!(matrix
+            .rotate_cw()
+            .rotate_cw()
+            .rows()
+            .eq(flipped.flip_horizontal().rows()));
+    }
+
+    #[test]
+    fn coordinates_signum_and_wrap() {
+        assert_eq!(
+            Coordinates { x: -7, y: 3 }.signum(),
+            Coordinates { x: -1, y: 1 }
+        );
+        assert_eq!(
+            Coordinates { x: 0usize, y: 9 }.signum(),
+            Coordinates { x: 0, y: 1 }
+        );
+
+        let size = Coordinates { x: 5, y: 4 };
+        assert_eq!(
+            Coordinates { x: -1, y: 4 }.wrap(size),
+            Coordinates { x: 4, y: 0 }
+        );
+        assert_eq!(
+            Coordinates { x: 12, y: -9 }.wrap(size),
+            Coordinates { x: 2, y: 3 }
+        );
+    }
+
+    #[test]
+    fn matrix_neighborhoods() {
+        let matrix = numbered_matrix();
+        let sorted = |neighbors: &mut dyn Iterator<Item = Coordinates>| {
+            let mut neighbors: Vec<_> = neighbors.map(|c| (c.x, c.y)).collect();
+            neighbors.sort();
+            neighbors
+        };
+
+        assert_eq!(
+            sorted(&mut matrix.neighbors8(Coordinates { x: 0, y: 0 })),
+            [(0, 1), (1, 0), (1, 1)]
+        );
+        assert_eq!(matrix.neighbors8(Coordinates { x: 2, y: 2 }).count(), 8);
+        assert_eq!(
+            sorted(&mut matrix.wrapping_neighbors(
+                Coordinates { x: 0, y: 0 },
+                Direction::ALL.map(|d| d.normal_vector()),
+            )),
+            [(0, 1), (0, 3), (1, 0), (4, 0)]
+        );
+    }
+
+    #[test]
+    fn direction_turns() {
+        for direction in Direction::ALL {
+            assert_eq!(direction.turn_left().turn_right(), direction);
+            assert_eq!(direction.turn_right().turn_right(), direction.opposite());
+            assert_eq!(direction.opposite().opposite(), direction);
+            assert_eq!(
+                direction.opposite().normal_vector(),
+                Coordinates::default() - direction.normal_vector()
+            );
+        }
+
+        assert_eq!(Direction::Up.turn_left(), Direction::Left);
+        assert_eq!(Direction::Up.turn_right(), Direction::Right);
+    }
+
+    #[test]
+    fn bounding_box_size_and_contains() {
+        let coords = [(2, -1), (-3, 4), (0, 0)].map(Coordinates::from);
+        let bounds = BoundingBox::for_coordinates(coords.iter());
+
+        assert_eq!(bounds.size(), Coordinates { x: 6, y: 6 });
+        assert!(coords.iter().all(|c| bounds.contains(*c)));
+        assert!(bounds.contains(Coordinates { x: -3, y: -1 }));
+        assert!(!bounds.contains(Coordinates { x: 3, y: 0 }));
+        assert!(!bounds.contains(Coordinates { x: 0, y: 5 }));
+    }
+
+    #[test]
+    fn sparse_grid_cells() {
+        let mut grid = SparseGrid::new('.');
+        assert!(grid.is_empty());
+        assert_eq!(grid.to_matrix().size(), Coordinates { x: 0, y: 0 });
+
+        grid.insert(Coordinates { x: -1, y: 2 }, '#');
+        grid.insert(Coordinates { x: 1, y: 3 }, 'o');
+        assert_eq!(grid.len(), 2);
+        assert!(grid.contains(Coordinates { x: 1, y: 3 }));
+        assert_eq!(grid[Coordinates { x: 0, y: 0 }], '.');
+
+        let matrix = grid.to_matrix();
+        assert_eq!(matrix.to_string(), "#..\n..o");
+
+        assert_eq!(grid.remove(Coordinates { x: 1, y: 3 }), Some('o'));
+        assert_eq!(grid.remove(Coordinates { x: 1, y: 3 }), None);
+        assert!(!grid.contains(Coordinates { x: 1, y: 3 }));
+        assert_eq!(grid.len(), 1);
+        assert_eq!(grid.to_matrix().to_string(), "#..\n...");
+    }
+
+    fn parse_digits(lines: &[&str]) -> Result<Matrix<u32>, GridParseError<&'static str>> {
+        Matrix::from_lines(lines, |_coords, ch| ch.to_digit(10).ok_or("Not a digit"))
+    }
+
+    #[test]
+    fn matrix_from_lines() {
+        let matrix = parse_digits(&["123", "456"]).unwrap();
+        assert_eq!(matrix.size(), Coordinates { x: 3, y: 2 });
+        assert_eq!(matrix[Coordinates { x: 2, y: 1 }], 6);
+
+        assert_eq!(
+            parse_digits(&[]).unwrap().size(),
+            Coordinates { x: 0, y: 0 }
+        );
+    }
+
+    #[test]
+    fn matrix_from_lines_reports_ragged_rows() {
+        assert_eq!(
+            parse_digits(&["123", "456", "78"]).err(),
+            Some(GridParseError::Ragged {
+                row: 2,
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            parse_digits(&["12", "345"]).err(),
+            Some(GridParseError::Ragged {
+                row: 1,
+                expected: 2,
+                found: 3
+            })
+        );
+    }
+
+    #[test]
+    fn matrix_from_lines_reports_invalid_cells() {
+        assert_eq!(
+            parse_digits(&["123", "4x6"]).err(),
+            Some(GridParseError::InvalidCell {
+                coords: Coordinates { x: 1, y: 1 },
+                ch: 'x',
+                error: "Not a digit"
+            })
+        );
+    }
+
+    #[test]
+    fn matrix_from_lines_collects_markers() {
+        let (matrix, markers) =
+            Matrix::from_lines_with_markers(["S.#", "#SE"], &['S', 'E'], |_coords, ch| {
+                Ok::<_, ()>(if ch == '#' { '#' } else { '.' })
+            })
+            .unwrap();
+
+        assert_eq!(matrix.to_string(), "..#\n#..");
+        assert_eq!(
+            markers.get(&'S'),
+            Some(&vec![
+                Coordinates { x: 0, y: 0 },
+                Coordinates { x: 1, y: 1 }
+            ])
+        );
+        assert_eq!(markers.get(&'E'), Some(&vec![Coordinates { x: 2, y: 1 }]));
+        assert_eq!(markers.get(&'#'), None);
+    }
 }
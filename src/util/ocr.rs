@@ -0,0 +1,182 @@
+//! Reads the block letters that some puzzles draw on a screen
+//! instead of printing their answer.
+
+use std::fmt;
+
+use super::Matrix;
+
+pub const GLYPH_WIDTH: usize = 4;
+pub const GLYPH_HEIGHT: usize = 6;
+
+/// Glyphs are separated by a single blank column.
+const GLYPH_SPACING: usize = GLYPH_WIDTH + 1;
+
+const GLYPHS: [(char, [&str; GLYPH_HEIGHT]); 17] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// Decodes a picture of lit pixels into the letters it spells, reading
+/// glyphs from left to right.
+pub fn decode(pixels: &Matrix<bool>) -> Result<String, OcrError> {
+    if pixels.height() != GLYPH_HEIGHT {
+        return Err(OcrError::WrongHeight(pixels.height()));
+    }
+
+    let mut letters = String::new();
+    let mut unrecognized = vec![];
+    for position in 0..pixels.width().div_ceil(GLYPH_SPACING) {
+        match recognize(pixels, position * GLYPH_SPACING) {
+            Some(letter) => letters.push(letter),
+            None => unrecognized.push(position),
+        }
+    }
+
+    if unrecognized.is_empty() {
+        Ok(letters)
+    } else {
+        Err(OcrError::UnrecognizedGlyphs(unrecognized))
+    }
+}
+
+/// Finds the letter whose glyph, followed by its blank separator column,
+/// starts at the given column. The separator may be missing after the
+/// last glyph.
+fn recognize(pixels: &Matrix<bool>, left: usize) -> Option<char> {
+    GLYPHS
+        .iter()
+        .find(|(_letter, glyph)| {
+            pixels.rows().zip(glyph).all(|(row, glyph_row)| {
+                glyph_row
+                    .chars()
+                    .chain(std::iter::once('.'))
+                    .enumerate()
+                    .all(|(x, ch)| match row.get(left + x) {
+                        Some(lit) => *lit == (ch == '#'),
+                        None => x == GLYPH_WIDTH,
+                    })
+            })
+        })
+        .map(|(letter, _glyph)| *letter)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OcrError {
+    WrongHeight(usize),
+    UnrecognizedGlyphs(Vec<usize>),
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OcrError::WrongHeight(height) => write!(
+                f,
+                "Letters must be {GLYPH_HEIGHT} pixels tall, but the picture is {height}"
+            ),
+            OcrError::UnrecognizedGlyphs(positions) => {
+                let positions = positions
+                    .iter()
+                    .map(|position| position.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "Unrecognized glyphs at positions {positions}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for OcrError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Coordinates;
+
+    fn picture(rows: &[&str]) -> Matrix<bool> {
+        Matrix::from_rows(
+            rows.iter()
+                .map(|row| row.chars().map(|c| c == '#').collect()),
+        )
+    }
+
+    /// The word "BACKFLIP" as drawn on a 40 pixel wide screen.
+    const BACKFLIP: [&str; 6] = [
+        "###...##...##..#..#.####.#.....###.###..",
+        "#..#.#..#.#..#.#.#..#....#......#..#..#.",
+        "###..#..#.#....##...###..#......#..#..#.",
+        "#..#.####.#....#.#..#....#......#..###..",
+        "#..#.#..#.#..#.#.#..#....#......#..#....",
+        "###..#..#..##..#..#.#....####..###.#....",
+    ];
+
+    #[test]
+    fn decodes_every_letter() {
+        assert_eq!(decode(&picture(&BACKFLIP)), Ok("BACKFLIP".to_owned()));
+
+        // Without a separator column after the last glyph
+        let jughoserz = [
+            "..##.#..#..##..#..#..##...###.####.###..####",
+            "...#.#..#.#..#.#..#.#..#.#....#....#..#....#",
+            "...#.#..#.#....####.#..#.#....###..#..#...#.",
+            "...#.#..#.#.##.#..#.#..#..##..#....###...#..",
+            "#..#.#..#.#..#.#..#.#..#....#.#....#.#..#...",
+            ".##...##...###.#..#..##..###..####.#..#.####",
+        ];
+        assert_eq!(decode(&picture(&jughoserz)), Ok("JUGHOSERZ".to_owned()));
+    }
+
+    #[test]
+    fn glyphs_are_well_formed_and_distinct() {
+        for (index, (letter, glyph)) in GLYPHS.iter().enumerate() {
+            assert!(
+                glyph
+                    .iter()
+                    .all(|row| row.len() == GLYPH_WIDTH && row.chars().all(|c| "#.".contains(c))),
+                "{letter}"
+            );
+            assert!(
+                GLYPHS[index + 1..]
+                    .iter()
+                    .all(|(_other, other)| other != glyph),
+                "{letter}"
+            );
+        }
+    }
+
+    #[test]
+    fn reports_unrecognized_glyph_positions() {
+        let mut pixels = picture(&BACKFLIP);
+        for x in [11, 26, 29] {
+            let coords = Coordinates { x, y: 0 };
+            pixels[coords] = !pixels[coords];
+        }
+
+        assert_eq!(
+            decode(&pixels),
+            Err(OcrError::UnrecognizedGlyphs(vec![2, 5]))
+        );
+    }
+
+    #[test]
+    fn reports_wrong_height() {
+        assert_eq!(
+            decode(&picture(&BACKFLIP[..5])),
+            Err(OcrError::WrongHeight(5))
+        );
+    }
+}
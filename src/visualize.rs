@@ -0,0 +1,73 @@
+use std::fmt;
+use std::io::IsTerminal;
+
+use crate::image::Image;
+
+/// Draws a picture of a day's solution for the terminal.
+pub trait Visualize {
+    fn visualize(&self, palette: &Palette) -> String;
+
+    /// Draws the same picture as an image, if the day supports it.
+    fn image(&self) -> Option<Image> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+    Cyan,
+    Gray,
+}
+
+impl Color {
+    fn ansi_code(&self) -> u8 {
+        match self {
+            Color::Red => 31,
+            Color::Green => 32,
+            Color::Yellow => 33,
+            Color::Cyan => 36,
+            Color::Gray => 90,
+        }
+    }
+}
+
+/// Paints text with ANSI colors, or leaves it as plain characters
+/// when colors are disabled.
+#[derive(Debug, Clone, Copy)]
+pub struct Palette {
+    colored: bool,
+}
+
+impl Palette {
+    pub fn new(colored: bool) -> Self {
+        Self { colored }
+    }
+
+    /// Uses colors only if stdout is a terminal.
+    pub fn for_stdout() -> Self {
+        Self::new(std::io::stdout().is_terminal())
+    }
+
+    pub fn is_colored(&self) -> bool {
+        self.colored
+    }
+
+    pub fn paint(&self, text: impl fmt::Display, color: Color) -> String {
+        if self.colored {
+            format!("\x1b[{}m{text}\x1b[0m", color.ansi_code())
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Joins rows of rendered cells into a picture, one row per line.
+pub fn render_rows<R: IntoIterator<Item = String>>(rows: impl IntoIterator<Item = R>) -> String {
+    rows.into_iter()
+        .map(|row| row.into_iter().collect::<String>())
+        .collect::<Vec<String>>()
+        .join("\n")
+}